@@ -0,0 +1,316 @@
+// SPDX-License-Identifier: MPL-2.0
+
+// Copyright (C) 2025 Soc Virnyl Estela and contributors
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Streaming encoders and decoders for each `crate::common::Compression`.
+//! These are what `crate::compress` and `crate::decompress` use internally.
+//! Use these directly to stream tarballs into any writer or from any reader
+//! e.g. a socket, a hasher or an in-memory buffer.
+
+use crate::common::Compression;
+use std::io::{
+    self,
+    BufRead,
+    Read,
+    Write,
+};
+#[allow(unused_imports)]
+use tracing::{
+    debug,
+    error,
+    info,
+    warn,
+};
+
+/// Zstd levels starting from this level are considered "ultra" levels.
+const ZSTD_ULTRA_LEVEL: i32 = 20;
+/// Window log used for long distance matching. This is the largest window
+/// log that zstd decoders accept without raising `--long` or
+/// `window_log_max`.
+const ZSTD_LONG_WINDOW_LOG: u32 = 27;
+/// Internal buffer size of the brotli encoder and decoder.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+/// Window size of the brotli encoder. This is the default of the `brotli` CLI.
+const BROTLI_LG_WINDOW: u32 = 22;
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Options passed to `Codec::encoder()` and `Codec::decoder()`. Decoders only
+/// use `dictionary`.
+pub struct CodecOptions<'d>
+{
+    /// Compression level. See `Compression::validate_level()`.
+    pub level: Option<i32>,
+    /// Number of threads for zst and xz. `0` lets libroast decide with
+    /// `crate::utils::available_threads()`.
+    pub threads: u32,
+    /// Zstd dictionary e.g. from `crate::compress::train_zstd_dict()`.
+    pub dictionary: Option<&'d [u8]>,
+}
+
+/// A compressing writer. Most encoders only write their trailer when finished
+/// so always call `Encoder::finish()` instead of dropping it.
+pub trait Encoder<W>: Write + Send
+{
+    /// Writes the remaining compressed data and returns the inner writer.
+    fn finish(self: Box<Self>) -> io::Result<W>;
+}
+
+/// Implemented for each `Compression`. New codecs plug in here.
+pub trait Codec
+{
+    /// Wraps `writer` in an encoder.
+    fn encoder<'a, W: Write + Send + 'a>(
+        &self,
+        writer: W,
+        options: &CodecOptions,
+    ) -> io::Result<Box<dyn Encoder<W> + 'a>>;
+
+    /// Wraps `reader` in a decoder.
+    fn decoder<'a, R: BufRead + 'a>(
+        &self,
+        reader: R,
+        options: &CodecOptions,
+    ) -> io::Result<Box<dyn Read + 'a>>;
+}
+
+impl Codec for Compression
+{
+    fn encoder<'a, W: Write + Send + 'a>(
+        &self,
+        writer: W,
+        options: &CodecOptions,
+    ) -> io::Result<Box<dyn Encoder<W> + 'a>>
+    {
+        let level = self.validate_level(options.level)?;
+        let encoder: Box<dyn Encoder<W> + 'a> = match (self, level)
+        {
+            (Compression::Gz, Some(level)) => Box::new(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::new(level as u32),
+            )),
+            (Compression::Xz, Some(level)) =>
+            {
+                // Crc32 is simpler/faster and often hardware accelerated.
+                use xz2::stream::{
+                    Check::Crc32,
+                    MtStreamBuilder,
+                };
+                let threads = crate::utils::resolve_threads(options.threads);
+                debug!(?threads);
+                let stream = MtStreamBuilder::new()
+                    .preset(level as u32)
+                    .threads(threads)
+                    .check(Crc32)
+                    .encoder()?;
+                Box::new(xz2::write::XzEncoder::new_stream(writer, stream))
+            }
+            (Compression::Zst | Compression::Zstd, Some(level)) =>
+            {
+                let mut encoder = zstd::Encoder::with_dictionary(
+                    writer,
+                    level,
+                    options.dictionary.unwrap_or_default(),
+                )?;
+                encoder.include_checksum(true)?;
+                if level >= ZSTD_ULTRA_LEVEL
+                {
+                    // Ultra levels benefit from a long window. We cap the window log to what
+                    // decoders accept by default so that the tarballs can still be
+                    // extracted without extra flags.
+                    debug!(level, "Enabling long distance matching for ultra level");
+                    encoder.long_distance_matching(true)?;
+                    encoder.window_log(ZSTD_LONG_WINDOW_LOG)?;
+                }
+                let threads = crate::utils::resolve_threads(options.threads);
+                debug!(?threads);
+                encoder.multithread(threads)?;
+                Box::new(encoder)
+            }
+            (Compression::Bz2, Some(level)) => Box::new(bzip2::write::BzEncoder::new(
+                writer,
+                bzip2::Compression::new(level as u32),
+            )),
+            (Compression::Lz4, Some(level)) => Box::new(
+                lz4::EncoderBuilder::new()
+                    .level(level as u32)
+                    .checksum(lz4::ContentChecksum::ChecksumEnabled)
+                    .build(writer)?,
+            ),
+            (Compression::Lz, Some(level)) => Box::new(lzma_rust2::LzipWriter::new(
+                writer,
+                lzma_rust2::LzipOptions::with_preset(level as u32),
+            )),
+            (Compression::Lzma, Some(level)) =>
+            {
+                use xz2::stream::{
+                    LzmaOptions,
+                    Stream,
+                };
+                let options = LzmaOptions::new_preset(level as u32)?;
+                let stream = Stream::new_lzma_encoder(&options)?;
+                Box::new(xz2::write::XzEncoder::new_stream(writer, stream))
+            }
+            (Compression::Br, Some(level)) =>
+            {
+                Box::new(BrotliEncoder(brotli::CompressorWriter::new(
+                    ErrorRecorder { inner: writer, error: None },
+                    BROTLI_BUFFER_SIZE,
+                    level as u32,
+                    BROTLI_LG_WINDOW,
+                )))
+            }
+            (Compression::Not, _) => Box::new(Uncompressed(writer)),
+            (_, None) =>
+            {
+                let msg = format!("THIS IS A BUG. No compression level for {}.", self);
+                error!(msg);
+                return Err(io::Error::other(msg));
+            }
+        };
+        Ok(encoder)
+    }
+
+    fn decoder<'a, R: BufRead + 'a>(
+        &self,
+        reader: R,
+        options: &CodecOptions,
+    ) -> io::Result<Box<dyn Read + 'a>>
+    {
+        let decoder: Box<dyn Read + 'a> = match self
+        {
+            // Archives with multiple concatenated gzip members e.g. from `pigz` are read
+            // until the last member.
+            Compression::Gz => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+            Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+            // Zstd reads concatenated frames until the end by default.
+            Compression::Zst | Compression::Zstd => Box::new(zstd::Decoder::with_dictionary(
+                reader,
+                options.dictionary.unwrap_or_default(),
+            )?),
+            Compression::Bz2 => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+            Compression::Lz4 => Box::new(lz4::Decoder::new(reader)?),
+            Compression::Lz => Box::new(lzma_rust2::LzipReader::new(reader)),
+            Compression::Lzma =>
+            {
+                let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)?;
+                Box::new(xz2::bufread::XzDecoder::new_stream(reader, stream))
+            }
+            Compression::Br => Box::new(brotli::Decompressor::new(reader, BROTLI_BUFFER_SIZE)),
+            Compression::Not => Box::new(reader),
+        };
+        Ok(decoder)
+    }
+}
+
+/// Passthrough "encoder" for uncompressed tarballs.
+struct Uncompressed<W>(W);
+
+impl<W: Write> Write for Uncompressed<W>
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.write(buf) }
+
+    fn flush(&mut self) -> io::Result<()> { self.0.flush() }
+}
+
+impl<W: Write + Send> Encoder<W> for Uncompressed<W>
+{
+    fn finish(self: Box<Self>) -> io::Result<W>
+    {
+        let mut writer = self.0;
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write + Send> Encoder<W> for flate2::write::GzEncoder<W>
+{
+    fn finish(self: Box<Self>) -> io::Result<W> { flate2::write::GzEncoder::finish(*self) }
+}
+
+impl<W: Write + Send> Encoder<W> for xz2::write::XzEncoder<W>
+{
+    fn finish(self: Box<Self>) -> io::Result<W> { xz2::write::XzEncoder::finish(*self) }
+}
+
+impl<W: Write + Send> Encoder<W> for zstd::Encoder<'static, W>
+{
+    fn finish(self: Box<Self>) -> io::Result<W> { zstd::Encoder::finish(*self) }
+}
+
+impl<W: Write + Send> Encoder<W> for bzip2::write::BzEncoder<W>
+{
+    fn finish(self: Box<Self>) -> io::Result<W> { bzip2::write::BzEncoder::finish(*self) }
+}
+
+impl<W: Write + Send> Encoder<W> for lz4::Encoder<W>
+{
+    fn finish(self: Box<Self>) -> io::Result<W>
+    {
+        let (writer, result) = lz4::Encoder::finish(*self);
+        result.map(|_| writer)
+    }
+}
+
+impl<W: Write + Send> Encoder<W> for lzma_rust2::LzipWriter<W>
+{
+    fn finish(self: Box<Self>) -> io::Result<W> { lzma_rust2::LzipWriter::finish(*self) }
+}
+
+/// Remembers the first error of `inner` so that it can be returned after the
+/// error was discarded by a wrapping writer.
+struct ErrorRecorder<W>
+{
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> ErrorRecorder<W>
+{
+    fn record(&mut self, err: io::Error) -> io::Error
+    {
+        let copy = io::Error::new(err.kind(), err.to_string());
+        self.error.get_or_insert(err);
+        copy
+    }
+}
+
+impl<W: Write> Write for ErrorRecorder<W>
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>
+    {
+        self.inner.write(buf).map_err(|err| self.record(err))
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.inner.flush().map_err(|err| self.record(err)) }
+}
+
+/// Brotli encoder whose `finish()` fails if writing the trailer failed.
+/// `brotli::CompressorWriter::into_inner()` finishes the stream but discards
+/// errors so they are taken from the `ErrorRecorder` instead.
+struct BrotliEncoder<W: Write>(brotli::CompressorWriter<ErrorRecorder<W>>);
+
+impl<W: Write> Write for BrotliEncoder<W>
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.write(buf) }
+
+    fn flush(&mut self) -> io::Result<()> { self.0.flush() }
+}
+
+impl<W: Write + Send> Encoder<W> for BrotliEncoder<W>
+{
+    fn finish(self: Box<Self>) -> io::Result<W>
+    {
+        let ErrorRecorder { mut inner, error } = self.0.into_inner();
+        if let Some(err) = error
+        {
+            error!(?err, "Failed to finish the brotli stream");
+            return Err(err);
+        }
+        inner.flush()?;
+        Ok(inner)
+    }
+}
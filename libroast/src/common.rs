@@ -5,13 +5,31 @@
 // This Source Code Form is subject to the terms of the Mozilla Public
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
+use crate::consts::{
+    BR_MIME,
+    BZ2_MIME,
+    GZ_MIME,
+    LZ4_MIME,
+    LZIP_MIME,
+    LZMA_MIME,
+    SEVENZ_MIME,
+    TAR_MIME,
+    XZ_MIME,
+    ZIP_MIME,
+    ZST_MIME,
+};
 use clap::ValueEnum;
 use std::{
     fmt::{
         self,
         Display,
     },
-    path::PathBuf,
+    io,
+    ops::RangeInclusive,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 #[allow(unused_imports)]
 use tracing::{
@@ -21,9 +39,10 @@ use tracing::{
     warn,
 };
 
-#[derive(ValueEnum, Default, Debug, Clone, Copy)]
+#[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq, Eq)]
 /// Compression options to choose. `Compression::Zst` is the default. `Zst` and
-/// `Zstd` are the same.
+/// `Zstd` are the same. `Lz` is lzip while `Lzma` is the legacy lzma-alone
+/// format. `Br` is brotli.
 pub enum Compression
 {
     Gz,
@@ -32,11 +51,108 @@ pub enum Compression
     Zst,
     Zstd,
     Bz2,
+    Lz4,
+    Lz,
+    Lzma,
+    Br,
     Not,
 }
 
 impl Compression
 {
+    /// The compression level used if no level was passed.
+    pub fn default_level(&self) -> Option<i32>
+    {
+        match self
+        {
+            Compression::Gz => Some(6),
+            Compression::Xz => Some(6),
+            Compression::Zst | Compression::Zstd => Some(19),
+            Compression::Bz2 => Some(9),
+            Compression::Lz4 => Some(1),
+            Compression::Lz => Some(6),
+            Compression::Lzma => Some(6),
+            Compression::Br => Some(11),
+            Compression::Not => None,
+        }
+    }
+
+    /// The range of valid compression levels for each compression option.
+    /// Uncompressed tarballs have no levels. Zstd levels from 20 to 22 are
+    /// "ultra" levels. Negative zstd levels are the fast levels.
+    pub fn level_range(&self) -> Option<RangeInclusive<i32>>
+    {
+        match self
+        {
+            Compression::Gz => Some(0..=9),
+            Compression::Xz => Some(0..=9),
+            Compression::Zst | Compression::Zstd => Some(zstd::compression_level_range()),
+            Compression::Bz2 => Some(1..=9),
+            Compression::Lz4 => Some(1..=12),
+            Compression::Lz => Some(0..=9),
+            Compression::Lzma => Some(0..=9),
+            Compression::Br => Some(0..=11),
+            Compression::Not => None,
+        }
+    }
+
+    /// Returns the compression level to use. Falls back to
+    /// `Compression::default_level()` if there is no level provided.
+    /// Invalid levels are rejected with `io::ErrorKind::InvalidInput`.
+    pub fn validate_level(&self, level: Option<i32>) -> io::Result<Option<i32>>
+    {
+        let Some(level) = level
+        else
+        {
+            return Ok(self.default_level());
+        };
+        match self.level_range()
+        {
+            Some(range) if range.contains(&level) => Ok(Some(level)),
+            Some(range) =>
+            {
+                let msg = format!(
+                    "Invalid compression level `{}` for {}. Valid levels are from {} to {}.",
+                    level,
+                    self,
+                    range.start(),
+                    range.end()
+                );
+                error!(msg);
+                Err(crate::Error::invalid_argument(msg).into())
+            }
+            None =>
+            {
+                let msg = format!("Compression levels are not supported for {}.", self);
+                error!(msg);
+                Err(crate::Error::invalid_argument(msg).into())
+            }
+        }
+    }
+
+    /// Splits a file name e.g. `source.tgz` into the name without the tarball
+    /// extension and the compression it stands for. Case-insensitive. See
+    /// `TARBALL_EXTENSIONS` for the supported extensions.
+    pub fn split_extension(file_name: &str) -> Option<(&str, Compression)>
+    {
+        let lowercase = file_name.to_ascii_lowercase();
+        TARBALL_EXTENSIONS.iter().find_map(|(extension, compression)| {
+            lowercase
+                .strip_suffix(extension)
+                .filter(|name| !name.is_empty())
+                .map(|name| (&file_name[..name.len()], *compression))
+        })
+    }
+
+    /// The compression of a tarball based on the file extension of `path` e.g.
+    /// `.tar.gz` or `.tgz`. Returns `None` if it is not a tarball extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Compression>
+    {
+        let file_name = path.as_ref().file_name()?.to_string_lossy();
+        Compression::split_extension(&file_name).map(|(_, compression)| compression)
+    }
+
+    /// The conventional file extension of a tarball with this compression.
     pub fn to_extension(&self) -> String
     {
         match self
@@ -44,12 +160,41 @@ impl Compression
             Compression::Gz => ".tar.gz",
             Compression::Xz => ".tar.xz",
             Compression::Zst | Compression::Zstd => ".tar.zst",
-            Compression::Bz2 => ".tar.bz",
+            Compression::Bz2 => ".tar.bz2",
+            Compression::Lz4 => ".tar.lz4",
+            Compression::Lz => ".tar.lz",
+            Compression::Lzma => ".tar.lzma",
+            Compression::Br => ".tar.br",
             Compression::Not => ".tar",
         }
         .to_string()
     }
 }
+
+/// File extensions of tarballs and their short aliases as used by GNU tar.
+const TARBALL_EXTENSIONS: &[(&str, Compression)] = &[
+    (".tar.gz", Compression::Gz),
+    (".tar.xz", Compression::Xz),
+    (".tar.zst", Compression::Zst),
+    (".tar.zstd", Compression::Zst),
+    (".tar.bz2", Compression::Bz2),
+    (".tar.bz", Compression::Bz2),
+    (".tar.lz4", Compression::Lz4),
+    (".tar.lzma", Compression::Lzma),
+    (".tar.lz", Compression::Lz),
+    (".tar.br", Compression::Br),
+    (".tgz", Compression::Gz),
+    (".taz", Compression::Gz),
+    (".txz", Compression::Xz),
+    (".tzst", Compression::Zst),
+    (".tbz2", Compression::Bz2),
+    (".tbz", Compression::Bz2),
+    (".tb2", Compression::Bz2),
+    (".tz2", Compression::Bz2),
+    (".tlz", Compression::Lzma),
+    (".tar", Compression::Not),
+];
+
 impl Display for Compression
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
@@ -60,20 +205,161 @@ impl Display for Compression
             Compression::Xz => "xz",
             Compression::Zst | Compression::Zstd => "zst",
             Compression::Bz2 => "bz2",
+            Compression::Lz4 => "lz4",
+            Compression::Lz => "lz",
+            Compression::Lzma => "lzma",
+            Compression::Br => "br",
             Compression::Not => "tar (uncompressed)",
         };
         write!(f, "{}", msg)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Archive formats other than tarballs. These can only be read. See
+/// `crate::decompress::archive_as_tar()`.
+pub enum ArchiveFormat
+{
+    Zip,
+    SevenZ,
+}
+
+impl ArchiveFormat
+{
+    pub fn to_extension(&self) -> String
+    {
+        match self
+        {
+            ArchiveFormat::Zip => ".zip",
+            ArchiveFormat::SevenZ => ".7z",
+        }
+        .to_string()
+    }
+}
+
+impl Display for ArchiveFormat
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let msg = match self
+        {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::SevenZ => "7z",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
 #[derive(Debug)]
-/// Representation of supported formats. Either an archive or a directory.
+/// Representation of supported formats. Either a tarball, a zip or 7z
+/// archive or a directory.
 pub enum SupportedFormat
 {
     Compressed(Compression, PathBuf),
+    Archive(ArchiveFormat, PathBuf),
     Dir(PathBuf),
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+/// Formats that `raw` and `recomprizz` can read. These are detected with
+/// `crate::utils::sniff_format()` unless passed with `--format`.
+pub enum InputFormat
+{
+    Gz,
+    Xz,
+    Zst,
+    Bz2,
+    Lz4,
+    Lz,
+    Lzma,
+    Br,
+    Tar,
+    Zip,
+    #[value(name = "7z")]
+    SevenZ,
+}
+
+impl InputFormat
+{
+    /// Pairs the format with the archive at `path`.
+    pub fn to_supported_format(self, path: PathBuf) -> SupportedFormat
+    {
+        match self
+        {
+            InputFormat::Gz => SupportedFormat::Compressed(Compression::Gz, path),
+            InputFormat::Xz => SupportedFormat::Compressed(Compression::Xz, path),
+            InputFormat::Zst => SupportedFormat::Compressed(Compression::Zst, path),
+            InputFormat::Bz2 => SupportedFormat::Compressed(Compression::Bz2, path),
+            InputFormat::Lz4 => SupportedFormat::Compressed(Compression::Lz4, path),
+            InputFormat::Lz => SupportedFormat::Compressed(Compression::Lz, path),
+            InputFormat::Lzma => SupportedFormat::Compressed(Compression::Lzma, path),
+            InputFormat::Br => SupportedFormat::Compressed(Compression::Br, path),
+            InputFormat::Tar => SupportedFormat::Compressed(Compression::Not, path),
+            InputFormat::Zip => SupportedFormat::Archive(ArchiveFormat::Zip, path),
+            InputFormat::SevenZ => SupportedFormat::Archive(ArchiveFormat::SevenZ, path),
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str
+    {
+        match self
+        {
+            InputFormat::Gz => GZ_MIME,
+            InputFormat::Xz => XZ_MIME,
+            InputFormat::Zst => ZST_MIME,
+            InputFormat::Bz2 => BZ2_MIME,
+            InputFormat::Lz4 => LZ4_MIME,
+            InputFormat::Lz => LZIP_MIME,
+            InputFormat::Lzma => LZMA_MIME,
+            InputFormat::Br => BR_MIME,
+            InputFormat::Tar => TAR_MIME,
+            InputFormat::Zip => ZIP_MIME,
+            InputFormat::SevenZ => SEVENZ_MIME,
+        }
+    }
+}
+
+impl From<Compression> for InputFormat
+{
+    fn from(compression: Compression) -> Self
+    {
+        match compression
+        {
+            Compression::Gz => InputFormat::Gz,
+            Compression::Xz => InputFormat::Xz,
+            Compression::Zst | Compression::Zstd => InputFormat::Zst,
+            Compression::Bz2 => InputFormat::Bz2,
+            Compression::Lz4 => InputFormat::Lz4,
+            Compression::Lz => InputFormat::Lz,
+            Compression::Lzma => InputFormat::Lzma,
+            Compression::Br => InputFormat::Br,
+            Compression::Not => InputFormat::Tar,
+        }
+    }
+}
+
+impl Display for InputFormat
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let msg = match self
+        {
+            InputFormat::Gz => "gz",
+            InputFormat::Xz => "xz",
+            InputFormat::Zst => "zst",
+            InputFormat::Bz2 => "bz2",
+            InputFormat::Lz4 => "lz4",
+            InputFormat::Lz => "lz",
+            InputFormat::Lzma => "lzma",
+            InputFormat::Br => "br",
+            InputFormat::Tar => "tar",
+            InputFormat::Zip => "zip",
+            InputFormat::SevenZ => "7z",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
 impl std::error::Error for UnsupportedFormat {}
 
 #[derive(Debug)]
@@ -81,13 +367,21 @@ impl std::error::Error for UnsupportedFormat {}
 /// errors.
 pub struct UnsupportedFormat
 {
+    /// The file extension or a description of what was passed.
     pub ext: String,
+    /// The MIME type detected from the magic bytes if any.
+    pub mime_type: Option<String>,
 }
 
 impl Display for UnsupportedFormat
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
     {
-        write!(f, "Unsupported archive format: {}", self.ext)
+        write!(f, "Unsupported archive format: {}", self.ext)?;
+        if let Some(mime_type) = &self.mime_type
+        {
+            write!(f, " (detected MIME type: {})", mime_type)?;
+        }
+        Ok(())
     }
 }
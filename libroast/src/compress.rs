@@ -5,6 +5,23 @@
 // This Source Code Form is subject to the terms of the Mozilla Public
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
+use crate::{
+    codec::{
+        Codec,
+        CodecOptions,
+    },
+    common::{
+        Compression,
+        SupportedFormat,
+    },
+    manifest::{
+        EntryKind,
+        Manifest,
+        ManifestEntry,
+    },
+    utils::is_supported_format,
+};
+use hifitime::Epoch;
 use rayon::prelude::*;
 use std::{
     fs::{
@@ -12,12 +29,10 @@ use std::{
     },
     io::{
         self,
+        Seek,
         Write,
     },
-    path::{
-        Path,
-        PathBuf,
-    },
+    path::Path,
 };
 use tar;
 #[allow(unused_imports)]
@@ -29,110 +44,403 @@ use tracing::{
     warn,
 };
 
+/// Size of each sample chunk when training a zstd dictionary.
+const ZSTD_DICT_SAMPLE_SIZE: usize = 128 * 1024;
+/// Deflate levels accepted for zip archives.
+const ZIP_LEVELS: std::ops::RangeInclusive<i32> = 0..=9;
+
 /// Create a deterministic tar-header for creating reproducible tarballs. Used
-/// for `super::tar_builder()` for the parameter `reproducible` to generate
-/// deterministic output.
-fn create_deterministic_header(path: impl AsRef<Path>) -> Result<tar::Header, io::Error>
+/// for `super::manifest_tar_builder()` for the parameter `reproducible` to
+/// generate deterministic output.
+fn create_deterministic_header(metadata: &fs::Metadata) -> tar::Header
 {
-    let metadata = path.as_ref().symlink_metadata()?;
     let mut h = tar::Header::new_gnu();
-    h.set_metadata_in_mode(&metadata, tar::HeaderMode::Deterministic);
+    h.set_metadata_in_mode(metadata, tar::HeaderMode::Deterministic);
     h.set_mtime(0);
     h.set_uid(0);
     h.set_gid(0);
     h.set_cksum();
-    Ok(h)
+    h
 }
 
-/// Helper function to help add other paths to the archive for
-/// `super::tar_builder()`.
-fn add_path_to_archive<T: Write>(
+/// Helper function to add an entry of a manifest to the archive for
+/// `super::manifest_tar_builder()`. The entry is streamed from its source.
+fn add_entry_to_archive<T: Write>(
     builder: &mut tar::Builder<T>,
-    additional_path: &Path,
-    target_dir: &Path,
+    entry: &ManifestEntry,
     reproducible: bool,
 ) -> io::Result<()>
 {
-    let additional_path = additional_path.canonicalize().unwrap_or(additional_path.to_path_buf());
-    let target_dir = target_dir.canonicalize().unwrap_or(target_dir.to_path_buf());
+    let metadata = entry.metadata().inspect_err(|err| {
+        error!(?err, "Unable to read the metadata of {}", entry.source.display());
+    })?;
     let mut h = if reproducible
     {
-        create_deterministic_header(&additional_path)?
+        create_deterministic_header(&metadata)
     }
     else
     {
-        let metadata = additional_path.symlink_metadata()?;
         let mut hsub = tar::Header::new_gnu();
         hsub.set_metadata(&metadata);
         hsub
     };
-    // Each path is relative to prjdir. So we can split the
-    // prjdir prefix to get the relative archive path.
-    let subpath = &additional_path.strip_prefix(&target_dir).map_err(|err| {
-        error!(
-            ?err,
-            "THIS IS A BUG. Unable to proceed. {} is not within {}.",
-            additional_path.to_string_lossy(),
-            target_dir.to_string_lossy()
-        );
-        io::Error::other(additional_path.to_string_lossy())
-    })?;
+    let subpath = &entry.archive_path;
 
-    if additional_path.is_file()
+    match entry.kind
     {
-        let src = std::fs::File::open(&additional_path).map(std::io::BufReader::new)?;
-        builder.append_data(&mut h, subpath, src)?;
+        EntryKind::Symlink =>
+        {
+            let target = entry.source.read_link()?;
+            builder.append_link(&mut h, subpath, target)?;
+        }
+        EntryKind::File =>
+        {
+            let src = std::fs::File::open(&entry.source).map(std::io::BufReader::new)?;
+            builder.append_data(&mut h, subpath, src)?;
+        }
+        EntryKind::Dir =>
+        {
+            // Adding the dir as an empty node
+            builder.append_data(&mut h, subpath, std::io::Cursor::new([]))?;
+        }
     }
-    else if additional_path.is_symlink()
+    trace!("Added {} to archive as {}", entry.source.to_string_lossy(), subpath.display());
+    Ok(())
+}
+
+/// Entries of `manifest` sorted by their archive path as a string. This is the
+/// order of the entries in tarballs and zip archives.
+fn sorted_entries(manifest: &Manifest) -> Vec<&ManifestEntry>
+{
+    let mut entries: Vec<&ManifestEntry> = manifest.entries().collect();
+    entries.par_sort_by(|a, b| {
+        a.archive_path.to_string_lossy().cmp(&b.archive_path.to_string_lossy())
+    });
+    entries
+}
+
+/// Produces a tarball of the entries of `manifest`. Each entry is read
+/// straight from its source.
+pub fn manifest_tar_builder<T: Write + std::marker::Send>(
+    builder: &mut tar::Builder<T>,
+    manifest: &Manifest,
+    reproducible: bool,
+) -> io::Result<()>
+{
+    // Only metadata that is directly relevant to the identity of a file will be
+    // included. In particular, ownership and mod/access times are excluded.
+    builder.mode(tar::HeaderMode::Deterministic);
+    sorted_entries(manifest).into_iter().try_for_each(|entry| {
+        debug!(?entry);
+        add_entry_to_archive(builder, entry, reproducible)
+    })?;
+
+    builder.finish()
+}
+
+/// Helper function to produce a tarball. `tar_into()` uses this function as a
+/// "tar builder" for the encoder of each `crate::codec::Codec`. Each path in
+/// `archive_files` is archived relative to `target_dir`.
+pub fn tar_builder<T: Write + std::marker::Send>(
+    builder: &mut tar::Builder<T>,
+    target_dir: &Path,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+) -> io::Result<()>
+{
+    let manifest = Manifest::from_files(target_dir, archive_files)?;
+    manifest_tar_builder(builder, &manifest, reproducible)
+}
+
+/// Streams a tarball of the entries of `manifest` compressed with
+/// `compression` into any `writer`. Returns the writer once the compressed
+/// stream is finished.
+pub fn manifest_tar_into<W: Write + Send>(
+    writer: W,
+    compression: Compression,
+    manifest: &Manifest,
+    reproducible: bool,
+    options: &CodecOptions,
+) -> io::Result<W>
+{
+    let encoder = compression.encoder(writer, options)?;
+    let mut builder = tar::Builder::new(encoder);
+    manifest_tar_builder(&mut builder, manifest, reproducible)?;
+    builder.into_inner()?.finish()
+}
+
+/// Streams a tarball compressed with `compression` into any `writer` e.g. a
+/// socket, a hasher or an in-memory buffer. Returns the writer once the
+/// compressed stream is finished.
+pub fn tar_into<W: Write + Send>(
+    writer: W,
+    compression: Compression,
+    target_dir: impl AsRef<Path>,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+    options: &CodecOptions,
+) -> io::Result<W>
+{
+    let manifest = Manifest::from_files(target_dir.as_ref(), archive_files)?;
+    manifest_tar_into(writer, compression, &manifest, reproducible, options)
+}
+
+/// Writes a file at `outpath` with `write`. The file is written to a temporary
+/// file next to `outpath` first. It is only renamed to `outpath` once it is
+/// complete and synced to disk. On unix, the parent directory is synced after
+/// the rename too. On failure, `outpath` is left untouched.
+fn write_atomically(
+    outpath: &Path,
+    write: impl FnOnce(&mut fs::File) -> io::Result<()>,
+) -> io::Result<()>
+{
+    let parent = match outpath.parent()
+    {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".roast-").suffix(".part");
+    // Temporary files are only readable by the owner by default. Use the same
+    // permissions that `fs::File::create()` uses.
+    #[cfg(unix)]
     {
-        let target = additional_path.read_link()?;
-        builder.append_link(&mut h, subpath, target)?;
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(fs::Permissions::from_mode(0o666));
     }
-    else if additional_path.is_dir()
+    let mut outfile = builder
+        .tempfile_in(parent)
+        .inspect_err(|_| error!(?outpath, "Unable to create output file"))?;
+    // The temporary file is deleted on drop if anything fails.
+    write(outfile.as_file_mut())?;
+    outfile.as_file().sync_all()?;
+    outfile.persist(outpath).map_err(|err| {
+        error!(?err, ?outpath, "Unable to move output file into place");
+        err.error
+    })?;
+    // The rename is only durable once the directory entry is synced as well.
+    #[cfg(unix)]
+    fs::File::open(parent)
+        .and_then(|dir| dir.sync_all())
+        .inspect_err(|err| error!(?err, ?parent, "Unable to sync output directory"))?;
+    Ok(())
+}
+
+/// Produces a tarball compressed with `compression` at `outpath`. The tarball
+/// is written to a temporary file next to `outpath` first. It is only renamed
+/// to `outpath` once it is complete and synced to disk. On failure, `outpath`
+/// is left untouched.
+pub fn tarball(
+    compression: Compression,
+    outpath: impl AsRef<Path>,
+    target_dir: impl AsRef<Path>,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+    options: &CodecOptions,
+) -> io::Result<()>
+{
+    let manifest = Manifest::from_files(target_dir.as_ref(), archive_files)?;
+    manifest_tarball(compression, outpath, &manifest, reproducible, options)
+}
+
+/// Produces a tarball of the entries of `manifest` at `outpath`. Like
+/// `tarball()`, `outpath` is left untouched on failure.
+pub fn manifest_tarball(
+    compression: Compression,
+    outpath: impl AsRef<Path>,
+    manifest: &Manifest,
+    reproducible: bool,
+    options: &CodecOptions,
+) -> io::Result<()>
+{
+    // Reject invalid levels before creating the output file.
+    compression.validate_level(options.level)?;
+    write_atomically(outpath.as_ref(), |outtar| {
+        manifest_tar_into(outtar, compression, manifest, reproducible, options)?;
+        Ok(())
+    })
+}
+
+/// Rejects zip compression levels outside of `ZIP_LEVELS`.
+fn validate_zip_level(level: Option<i32>) -> io::Result<Option<i64>>
+{
+    match level
     {
-        // Adding the dir as an empty node
-        builder.append_data(&mut h, subpath, std::io::Cursor::new([]))?;
+        Some(level) if !ZIP_LEVELS.contains(&level) =>
+        {
+            let msg = format!(
+                "Invalid compression level `{}` for zip. Valid levels are from {} to {}.",
+                level,
+                ZIP_LEVELS.start(),
+                ZIP_LEVELS.end()
+            );
+            error!(msg);
+            Err(crate::Error::invalid_argument(msg).into())
+        }
+        level => Ok(level.map(i64::from)),
     }
+}
+
+/// Zip timestamp of `metadata`. Zip timestamps cannot represent dates before
+/// 1980 so those are clamped to 1980-01-01.
+fn zip_mtime(metadata: &fs::Metadata) -> zip::DateTime
+{
+    let Ok(mtime) = metadata.modified()
     else
     {
-        error!("Ignoring unexpected special file: {:?}", additional_path);
-    }
-    trace!("Added {} to archive", additional_path.to_string_lossy());
-    Ok(())
+        return zip::DateTime::default();
+    };
+    let seconds = match mtime.duration_since(std::time::UNIX_EPOCH)
+    {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => return zip::DateTime::default(),
+    };
+    let (year, month, day, hour, minute, second, _) =
+        Epoch::from_unix_seconds(seconds as f64).to_gregorian_utc();
+    u16::try_from(year)
+        .ok()
+        .and_then(|year| {
+            zip::DateTime::from_date_and_time(year, month, day, hour, minute, second).ok()
+        })
+        .unwrap_or_default()
 }
 
-/// Helper function to produce a tarball. `super::targz`, `super::tarbz2`,
-/// `super::tarzst`, `super::vanilla` and `super::tarxz` use this function as a
-/// "tar builder" since all of them have a trait bound for trait
-/// `std::io::Write` and have similar parameters.
-pub fn tar_builder<T: Write + std::marker::Send>(
-    builder: &mut tar::Builder<T>,
-    target_dir: &Path,
-    archive_files: &[impl AsRef<Path>],
+/// Helper function to add an entry of a manifest to a zip archive for
+/// `manifest_zip_into()`. This is the zip counterpart of
+/// `add_entry_to_archive()`. In reproducible mode, the
+/// permissions are normalized like `tar::HeaderMode::Deterministic` does and
+/// the timestamps are set to 1980-01-01, the earliest zip timestamp.
+fn add_entry_to_zip<W: Write + Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    entry: &ManifestEntry,
     reproducible: bool,
+    level: Option<i64>,
 ) -> io::Result<()>
 {
-    // Only metadata that is directly relevant to the identity of a file will be
-    // included. In particular, ownership and mod/access times are excluded.
-    builder.mode(tar::HeaderMode::Deterministic);
-    let mut archive_files: Vec<PathBuf> =
-        archive_files.iter().map(|p| p.as_ref().to_path_buf()).collect();
-    archive_files.par_sort_by(|a, b| a.to_string_lossy().cmp(&b.to_string_lossy()));
-    archive_files.iter().try_for_each(|f| {
-        let f = &Path::new(f);
-        debug!(?f);
-        if f.exists()
+    let metadata = entry.metadata().inspect_err(|err| {
+        error!(?err, "Unable to read the metadata of {}", entry.source.display());
+    })?;
+    let name = entry.archive_path.to_string_lossy();
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o7777
+    };
+    #[cfg(not(unix))]
+    let mode = if metadata.is_dir() { 0o755 } else { 0o644 };
+    let (mode, mtime) = if reproducible
+    {
+        let mode = if metadata.is_dir() || metadata.is_symlink() || mode & 0o111 != 0
         {
-            add_path_to_archive(builder, f, target_dir, reproducible)
+            0o755
         }
         else
         {
-            error!("THIS IS A BUG. Unable to proceed. {} does not exist.", f.to_string_lossy());
-            Err(io::Error::other(f.to_string_lossy()))
+            0o644
+        };
+        (mode, zip::DateTime::default())
+    }
+    else
+    {
+        (mode, zip_mtime(&metadata))
+    };
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .compression_level(level)
+        .unix_permissions(mode)
+        .last_modified_time(mtime)
+        .large_file(metadata.len() >= u64::from(u32::MAX));
+
+    match entry.kind
+    {
+        EntryKind::File =>
+        {
+            zip.start_file(name, options)?;
+            let mut src = std::fs::File::open(&entry.source).map(std::io::BufReader::new)?;
+            io::copy(&mut src, zip)?;
+        }
+        EntryKind::Symlink =>
+        {
+            let target = entry.source.read_link()?;
+            zip.add_symlink(name, target.to_string_lossy(), options)?;
         }
+        EntryKind::Dir =>
+        {
+            zip.add_directory(name, options)?;
+        }
+    }
+    trace!("Added {} to zip archive", entry.source.to_string_lossy());
+    Ok(())
+}
+
+/// Streams a zip archive of the entries of `manifest` into any seekable
+/// `writer`. Zip archives have the same contents as the tarballs of
+/// `manifest_tar_into()`. Entries are compressed with deflate at `level` which
+/// is from 0 to 9. Returns the writer once the archive is finished.
+pub fn manifest_zip_into<W: Write + Seek>(
+    writer: W,
+    manifest: &Manifest,
+    reproducible: bool,
+    level: Option<i32>,
+) -> io::Result<W>
+{
+    let level = validate_zip_level(level)?;
+    let mut zip = zip::ZipWriter::new(writer);
+    sorted_entries(manifest).into_iter().try_for_each(|entry| {
+        debug!(?entry);
+        add_entry_to_zip(&mut zip, entry, reproducible, level)
     })?;
+    Ok(zip.finish()?)
+}
 
-    builder.finish()
+/// Streams a zip archive of `archive_files` into any seekable `writer`. Each
+/// path is archived relative to `target_dir` like in `tar_into()`.
+pub fn zip_into<W: Write + Seek>(
+    writer: W,
+    target_dir: impl AsRef<Path>,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+    level: Option<i32>,
+) -> io::Result<W>
+{
+    // Reject invalid levels before touching the files.
+    validate_zip_level(level)?;
+    let manifest = Manifest::from_files(target_dir.as_ref(), archive_files)?;
+    manifest_zip_into(writer, &manifest, reproducible, level)
+}
+
+/// Produces a zip archive at `outpath` e.g. `source.zip`. Like `tarball()`,
+/// `outpath` is left untouched on failure.
+pub fn zip_archive(
+    outpath: impl AsRef<Path>,
+    target_dir: impl AsRef<Path>,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+    level: Option<i32>,
+) -> io::Result<()>
+{
+    // Reject invalid levels before touching the files.
+    validate_zip_level(level)?;
+    let manifest = Manifest::from_files(target_dir.as_ref(), archive_files)?;
+    manifest_zip_archive(outpath, &manifest, reproducible, level)
+}
+
+/// Produces a zip archive of the entries of `manifest` at `outpath`. Like
+/// `tarball()`, `outpath` is left untouched on failure.
+pub fn manifest_zip_archive(
+    outpath: impl AsRef<Path>,
+    manifest: &Manifest,
+    reproducible: bool,
+    level: Option<i32>,
+) -> io::Result<()>
+{
+    // Reject invalid levels before creating the output file.
+    validate_zip_level(level)?;
+    write_atomically(outpath.as_ref(), |outzip| {
+        let outzip = manifest_zip_into(io::BufWriter::new(outzip), manifest, reproducible, level)?;
+        outzip.into_inner().map_err(io::IntoInnerError::into_error)?;
+        Ok(())
+    })
 }
 
 /// Produces a Gz compressed tarball e.g. `source.tar.gz`.
@@ -141,94 +449,158 @@ pub fn targz(
     target_dir: impl AsRef<Path>,
     archive_files: &[impl AsRef<Path>],
     reproducible: bool,
+    level: Option<i32>,
 ) -> io::Result<()>
 {
-    use flate2::{
-        Compression,
-        write::GzEncoder,
-    };
-    let outtar = fs::File::create(outpath.as_ref())
-        .inspect_err(|_| error!(outpath = ?outpath.as_ref(), "Unable to create outtar"))?;
-    let encoder = GzEncoder::new(outtar, Compression::default());
-    let mut builder = tar::Builder::new(encoder);
-    tar_builder(&mut builder, target_dir.as_ref(), archive_files, reproducible)
+    let options = CodecOptions { level, ..Default::default() };
+    tarball(Compression::Gz, outpath, target_dir, archive_files, reproducible, &options)
 }
 
 /// Produces a Zst compressed tarball e.g. `source.tar.zst` or
-/// `source.tar.zstd`.
+/// `source.tar.zstd`. Ultra levels i.e. levels 20 to 22 also enable long
+/// distance matching. Passing `0` to `threads` lets libroast decide the
+/// number of workers with `crate::utils::available_threads()`. An optional
+/// `dictionary` e.g. from `train_zstd_dict()` can be passed. The same
+/// dictionary is required to decompress the tarball.
 pub fn tarzst(
     outpath: impl AsRef<Path>,
     target_dir: impl AsRef<Path>,
     archive_files: &[impl AsRef<Path>],
     reproducible: bool,
+    level: Option<i32>,
+    threads: u32,
+    dictionary: Option<&[u8]>,
 ) -> io::Result<()>
 {
-    use zstd::Encoder;
-    let outtar = fs::File::create(outpath.as_ref())
-        .inspect_err(|_| error!(outpath = ?outpath.as_ref(), "Unable to create outtar"))?;
-    let mut enc_builder = Encoder::new(outtar, 19)?;
-    enc_builder.include_checksum(true)?;
-    let threads: u32 = std::thread::available_parallelism()?.get() as u32;
-    enc_builder.multithread(threads)?;
-    let encoder = enc_builder.auto_finish();
-    let mut builder = tar::Builder::new(encoder);
-    tar_builder(&mut builder, target_dir.as_ref(), archive_files, reproducible)
+    let options = CodecOptions { level, threads, dictionary };
+    tarball(Compression::Zst, outpath, target_dir, archive_files, reproducible, &options)
 }
 
-/// Produces a Xz compressed tarball e.g. `source.tar.xz`.
+/// Produces a Xz compressed tarball e.g. `source.tar.xz`. Passing `0` to
+/// `threads` lets libroast decide the number of workers with
+/// `crate::utils::available_threads()`.
 pub fn tarxz(
     outpath: impl AsRef<Path>,
     target_dir: impl AsRef<Path>,
     archive_files: &[impl AsRef<Path>],
     reproducible: bool,
+    level: Option<i32>,
+    threads: u32,
 ) -> io::Result<()>
 {
-    // Crc32 is simpler/faster and often hardware accelerated.
-    use xz2::{
-        stream::{
-            Check::Crc32,
-            MtStreamBuilder,
-        },
-        write::XzEncoder,
-    };
-    let outtar = fs::File::create(outpath.as_ref())
-        .inspect_err(|_| error!(outpath = ?outpath.as_ref(), "Unable to create outtar"))?;
-    let threads: u32 = std::thread::available_parallelism()?.get() as u32;
-    let enc_builder = MtStreamBuilder::new().preset(6).threads(threads).check(Crc32).encoder()?;
-    let encoder = XzEncoder::new_stream(outtar, enc_builder);
-    let mut builder = tar::Builder::new(encoder);
-    tar_builder(&mut builder, target_dir.as_ref(), archive_files, reproducible)
+    let options = CodecOptions { level, threads, ..Default::default() };
+    tarball(Compression::Xz, outpath, target_dir, archive_files, reproducible, &options)
 }
 
-/// Produces a Bz compressed tarball e.g. `source.tar.bz`.
+/// Produces a Bz compressed tarball e.g. `source.tar.bz2`.
 pub fn tarbz2(
     outpath: impl AsRef<Path>,
     target_dir: impl AsRef<Path>,
     archive_files: &[impl AsRef<Path>],
     reproducible: bool,
+    level: Option<i32>,
 ) -> io::Result<()>
 {
-    use bzip2::{
-        Compression,
-        write::BzEncoder,
-    };
-    let outtar = fs::File::create(outpath.as_ref())
-        .inspect_err(|_| error!(outpath = ?outpath.as_ref(), "Unable to create outtar"))?;
-    let encoder = BzEncoder::new(outtar, Compression::best());
-    let mut builder = tar::Builder::new(encoder);
-    tar_builder(&mut builder, target_dir.as_ref(), archive_files, reproducible)
+    let options = CodecOptions { level, ..Default::default() };
+    tarball(Compression::Bz2, outpath, target_dir, archive_files, reproducible, &options)
+}
+
+/// Produces a Lz4 compressed tarball e.g. `source.tar.lz4`.
+pub fn tarlz4(
+    outpath: impl AsRef<Path>,
+    target_dir: impl AsRef<Path>,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+    level: Option<i32>,
+) -> io::Result<()>
+{
+    let options = CodecOptions { level, ..Default::default() };
+    tarball(Compression::Lz4, outpath, target_dir, archive_files, reproducible, &options)
+}
+
+/// Produces a Lzip compressed tarball e.g. `source.tar.lz`.
+pub fn tarlz(
+    outpath: impl AsRef<Path>,
+    target_dir: impl AsRef<Path>,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+    level: Option<i32>,
+) -> io::Result<()>
+{
+    let options = CodecOptions { level, ..Default::default() };
+    tarball(Compression::Lz, outpath, target_dir, archive_files, reproducible, &options)
+}
+
+/// Produces a legacy Lzma compressed tarball e.g. `source.tar.lzma`. This is
+/// the `lzma-alone` format and not the same as `source.tar.xz`.
+pub fn tarlzma(
+    outpath: impl AsRef<Path>,
+    target_dir: impl AsRef<Path>,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+    level: Option<i32>,
+) -> io::Result<()>
+{
+    let options = CodecOptions { level, ..Default::default() };
+    tarball(Compression::Lzma, outpath, target_dir, archive_files, reproducible, &options)
+}
+
+/// Produces a Brotli compressed tarball e.g. `source.tar.br`.
+pub fn tarbr(
+    outpath: impl AsRef<Path>,
+    target_dir: impl AsRef<Path>,
+    archive_files: &[impl AsRef<Path>],
+    reproducible: bool,
+    level: Option<i32>,
+) -> io::Result<()>
+{
+    let options = CodecOptions { level, ..Default::default() };
+    tarball(Compression::Br, outpath, target_dir, archive_files, reproducible, &options)
+}
+
+/// Trains a zstd dictionary that can be passed to `tarzst()` and
+/// `crate::decompress::tarzst()`. Samples are files such as earlier
+/// uncompressed tarballs. Compressed samples make a poor dictionary.
+pub fn train_zstd_dict(samples: &[impl AsRef<Path>], max_size: usize) -> io::Result<Vec<u8>>
+{
+    let mut sample_data: Vec<u8> = Vec::new();
+    let mut sample_sizes: Vec<usize> = Vec::new();
+    for sample in samples
+    {
+        let sample = sample.as_ref();
+        if let Ok(SupportedFormat::Compressed(compression, _)) = is_supported_format(sample)
+            && !matches!(compression, Compression::Not)
+        {
+            warn!(
+                "⚠️ Sample `{}` is {} compressed. Consider using uncompressed samples instead.",
+                sample.display(),
+                compression
+            );
+        }
+        let content = fs::read(sample)
+            .inspect_err(|err| error!(?err, "Unable to read sample {}", sample.display()))?;
+        // The dictionary builder works better with many small samples than with a
+        // few large tarballs.
+        content.chunks(ZSTD_DICT_SAMPLE_SIZE).for_each(|chunk| {
+            sample_sizes.push(chunk.len());
+            sample_data.extend_from_slice(chunk);
+        });
+    }
+    debug!(samples = sample_sizes.len(), bytes = sample_data.len());
+    zstd::dict::from_continuous(&sample_data, &sample_sizes, max_size)
+        .inspect_err(|err| error!(?err, "Unable to train zstd dictionary"))
 }
 
-/// Produces a uncompressed tarball e.g. `source.tar`.
+/// Produces a uncompressed tarball e.g. `source.tar`. Passing a `level` is
+/// an error since there is nothing to compress.
 pub fn vanilla(
     outpath: impl AsRef<Path>,
     target_dir: impl AsRef<Path>,
     archive_files: &[impl AsRef<Path>],
     reproducible: bool,
+    level: Option<i32>,
 ) -> io::Result<()>
 {
-    let outtar = fs::File::create(outpath.as_ref())
-        .inspect_err(|_| error!(outpath = ?outpath.as_ref(), "Unable to create outtar"))?;
-    let mut builder = tar::Builder::new(outtar);
-    tar_builder(&mut builder, target_dir.as_ref(), archive_files, reproducible)
+    let options = CodecOptions { level, ..Default::default() };
+    tarball(Compression::Not, outpath, target_dir, archive_files, reproducible, &options)
 }
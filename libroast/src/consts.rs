@@ -11,4 +11,9 @@ pub const ZST_MIME: &str = "application/zstd";
 pub const GZ_MIME: &str = "application/gzip";
 pub const BZ2_MIME: &str = "application/x-bzip2";
 pub const TAR_MIME: &str = "application/x-tar";
-pub const SUPPORTED_MIME_TYPES: &[&str] = &[XZ_MIME, ZST_MIME, GZ_MIME, BZ2_MIME, TAR_MIME];
+pub const LZ4_MIME: &str = "application/x-lz4";
+pub const LZIP_MIME: &str = "application/x-lzip";
+pub const LZMA_MIME: &str = "application/x-lzma";
+pub const BR_MIME: &str = "application/x-brotli";
+pub const ZIP_MIME: &str = "application/zip";
+pub const SEVENZ_MIME: &str = "application/x-7z-compressed";
@@ -6,13 +6,51 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-//! Decompress and extract core utility functions.
+//! Decompress and extract core utility functions. Entries that could escape
+//! the output directory are rejected or sanitized by default. See
+//! `ExtractOptions`.
 
+use crate::{
+    codec::{
+        Codec,
+        CodecOptions,
+    },
+    common::{
+        ArchiveFormat,
+        Compression,
+    },
+    error::Error,
+};
+use glob::{
+    MatchOptions,
+    Pattern,
+};
 use std::{
+    cell::Cell,
+    collections::VecDeque,
+    ffi::OsString,
     fs,
-    io,
-    io::Seek,
-    path::Path,
+    io::{
+        self,
+        BufRead,
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+    },
 };
 use tar;
 #[allow(unused_imports)]
@@ -20,89 +58,1183 @@ use tracing::{
     debug,
     error,
     info,
+    trace,
     warn,
 };
 
-/// Decompresses and extracts an archive with Gz.
-pub fn targz(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
+/// Permission bits that are dropped in safe mode i.e. setuid, setgid and
+/// sticky.
+const SPECIAL_MODE_BITS: u32 = 0o7000;
+/// Size of a file matched by `cat_from()` that is kept in memory before it is
+/// spooled to a temporary file.
+const CAT_SPOOL_SIZE: usize = 8 * 1024 * 1024;
+/// Seconds between the Windows epoch (1601-01-01) and the Unix epoch. 7z
+/// timestamps are counted in 100 nanosecond intervals since the former.
+const WINDOWS_EPOCH_OFFSET: u64 = 11_644_473_600;
+/// Number of decompressed bytes before the expansion ratio is checked. Tar
+/// headers and padding compress very well so small tarballs easily have
+/// high ratios.
+const RATIO_CHECK_THRESHOLD: u64 = 1024 * 1024;
+/// Number of symlinks followed by `resolves_outside()` before it gives up. This
+/// is the limit of Linux.
+const MAX_SYMLINKS_FOLLOWED: usize = 40;
+
+#[derive(Debug, Clone)]
+/// Policies applied while extracting a tarball with `unpack_from()` and
+/// `untar()`. The limits are unset by default.
+pub struct ExtractOptions
 {
-    use flate2::bufread::GzDecoder;
-    let mut src = io::BufReader::new(fs::File::open(srcpath.as_ref())?);
-    src.seek(io::SeekFrom::Start(0))?;
-    let enc = GzDecoder::new(src);
-    let mut ar = tar::Archive::new(enc);
-    ar.unpack(outdir.as_ref())?;
-    debug!(
-        "Successfully decompressed and extracted tape gz-compressed archive from {} to {}",
-        srcpath.as_ref().to_string_lossy(),
-        outdir.as_ref().to_string_lossy(),
-    );
+    /// Rejects or sanitizes entries of untrusted tarballs that could escape the
+    /// output directory. See `Violation`. Defaults to `true`.
+    pub safe: bool,
+    /// Maximum sum of the sizes of all entries in bytes.
+    pub max_total_size: Option<u64>,
+    /// Maximum number of entries.
+    pub max_entries: Option<u64>,
+    /// Maximum size of a single entry in bytes.
+    pub max_file_size: Option<u64>,
+    /// Maximum ratio of decompressed to compressed bytes. Only checked after
+    /// the first MiB of the decompressed stream.
+    pub max_ratio: Option<u64>,
+    /// Only extracts entries matching one of these patterns if not empty. See
+    /// `is_selected()`.
+    pub include: Vec<Pattern>,
+    /// Skips entries matching one of these patterns. See `is_selected()`.
+    pub exclude: Vec<Pattern>,
+    /// Number of leading path components to drop like GNU tar's
+    /// `--strip-components`. Entries with fewer components are skipped.
+    pub strip_components: usize,
+}
+
+impl Default for ExtractOptions
+{
+    fn default() -> Self
+    {
+        ExtractOptions {
+            safe: true,
+            max_total_size: None,
+            max_entries: None,
+            max_file_size: None,
+            max_ratio: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            strip_components: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The limits of `ExtractOptions`.
+pub enum Limit
+{
+    TotalSize,
+    Entries,
+    FileSize,
+    Ratio,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Representation of an exceeded extraction limit. Used for printing errors.
+pub struct LimitExceeded
+{
+    pub limit: Limit,
+    /// The configured maximum.
+    pub max: u64,
+}
+
+impl std::fmt::Display for LimitExceeded
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        let max = self.max;
+        match self.limit
+        {
+            Limit::TotalSize => write!(f, "Total unpacked size exceeds the limit of {} bytes", max),
+            Limit::Entries => write!(f, "Number of entries exceeds the limit of {}", max),
+            Limit::FileSize => write!(f, "Size of an entry exceeds the limit of {} bytes", max),
+            Limit::Ratio => write!(f, "Expansion ratio exceeds the limit of {}", max),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl From<LimitExceeded> for io::Error
+{
+    fn from(err: LimitExceeded) -> Self { io::Error::new(io::ErrorKind::InvalidData, err) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The kinds of entries that safe mode rejects or sanitizes.
+pub enum ViolationKind
+{
+    /// Absolute path. Sanitized by extracting it relative to the output
+    /// directory.
+    AbsolutePath,
+    /// Path with `..` components. Rejected.
+    ParentDir,
+    /// Symlink with an absolute target or a target outside the output
+    /// directory. Rejected.
+    SymlinkOutside,
+    /// Hardlink to a path outside the output directory. Rejected.
+    HardlinkOutside,
+    /// Character or block device or fifo. Rejected.
+    SpecialFile,
+    /// Setuid, setgid or sticky bit. Sanitized by dropping these bits.
+    SpecialModeBits,
+}
+
+impl ViolationKind
+{
+    /// Whether the entry is skipped instead of being sanitized.
+    pub fn is_rejected(&self) -> bool
+    {
+        !matches!(self, ViolationKind::AbsolutePath | ViolationKind::SpecialModeBits)
+    }
+}
+
+impl std::fmt::Display for ViolationKind
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        let msg = match self
+        {
+            ViolationKind::AbsolutePath =>
+            {
+                "absolute path, extracted relative to the output directory"
+            }
+            ViolationKind::ParentDir => "path contains `..`, skipped",
+            ViolationKind::SymlinkOutside =>
+            {
+                "symlink points outside of the output directory, skipped"
+            }
+            ViolationKind::HardlinkOutside =>
+            {
+                "hardlink points outside of the output directory, skipped"
+            }
+            ViolationKind::SpecialFile => "device node or fifo, skipped",
+            ViolationKind::SpecialModeBits => "setuid, setgid or sticky bit, dropped",
+        };
+        f.write_str(msg)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An entry that was rejected or sanitized in safe mode.
+pub struct Violation
+{
+    /// Path of the entry as stored in the tarball.
+    pub path: PathBuf,
+    pub kind: ViolationKind,
+}
+
+impl std::fmt::Display for Violation
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "Unsafe entry `{}`: {}", self.path.display(), self.kind)
+    }
+}
+
+/// Whether `target` resolves outside of the output directory when resolved
+/// from `base`. Both are relative to the output directory. This is purely
+/// lexical. Roast uses this for symlinks escaping the target directory too.
+pub(crate) fn escapes_outdir(base: &Path, target: &Path) -> bool
+{
+    let mut depth: usize = 0;
+    for component in base.components().chain(target.components())
+    {
+        match component
+        {
+            Component::Prefix(_) | Component::RootDir => return true,
+            Component::CurDir => continue,
+            Component::ParentDir =>
+            {
+                let Some(parent_depth) = depth.checked_sub(1)
+                else
+                {
+                    return true;
+                };
+                depth = parent_depth;
+            }
+            Component::Normal(_) => depth += 1,
+        }
+    }
+    false
+}
+
+/// Like `escapes_outdir()` but the symlinks that are already extracted to
+/// `outdir` are followed e.g. `e -> d/..` escapes if `d -> .` was extracted
+/// before.
+fn resolves_outside(outdir: &Path, base: &Path, target: &Path) -> bool
+{
+    let mut pending: VecDeque<OsString> = base
+        .components()
+        .chain(target.components())
+        .map(|c| c.as_os_str().to_os_string())
+        .collect();
+    let mut resolved = PathBuf::new();
+    let mut links_followed = 0;
+    while let Some(component) = pending.pop_front()
+    {
+        match Path::new(&component).components().next()
+        {
+            None | Some(Component::CurDir) => continue,
+            Some(Component::Prefix(_) | Component::RootDir) => return true,
+            Some(Component::ParentDir) =>
+            {
+                if !resolved.pop()
+                {
+                    return true;
+                }
+            }
+            Some(Component::Normal(name)) =>
+            {
+                let candidate = resolved.join(name);
+                let Ok(link_target) = fs::read_link(outdir.join(&candidate))
+                else
+                {
+                    resolved = candidate;
+                    continue;
+                };
+                // Most likely a symlink loop.
+                links_followed += 1;
+                if links_followed > MAX_SYMLINKS_FOLLOWED
+                {
+                    return true;
+                }
+                link_target
+                    .components()
+                    .rev()
+                    .for_each(|c| pending.push_front(c.as_os_str().to_os_string()));
+            }
+        }
+    }
+    false
+}
+
+/// Returns the violation of the path of an entry if it is absolute or has `..`
+/// components.
+fn path_violation(path: &Path) -> Option<ViolationKind>
+{
+    if path.components().any(|c| c == Component::ParentDir)
+    {
+        Some(ViolationKind::ParentDir)
+    }
+    else if path.has_root()
+    {
+        Some(ViolationKind::AbsolutePath)
+    }
+    else
+    {
+        None
+    }
+}
+
+/// Returns the violations of an entry in safe mode. `relative` is the path of
+/// the entry once extracted to `outdir`.
+fn check_entry<R: Read>(
+    entry: &tar::Entry<R>,
+    outdir: &Path,
+    relative: &Path,
+    strip_components: usize,
+) -> io::Result<Vec<ViolationKind>>
+{
+    let path = entry.path()?;
+    let mut violations: Vec<ViolationKind> = path_violation(&path).into_iter().collect();
+    let entry_type = entry.header().entry_type();
+    if entry_type.is_character_special() || entry_type.is_block_special() || entry_type.is_fifo()
+    {
+        violations.push(ViolationKind::SpecialFile);
+    }
+    else if let Some(link_name) = entry.link_name()?
+    {
+        // Tar resolves hardlinks from the output directory and symlinks from the
+        // directory of the entry.
+        if entry_type.is_hard_link()
+        {
+            let link_name = match strip_components
+            {
+                0 => Some(link_name.into_owned()),
+                _ => strip_leading(&link_name, strip_components),
+            };
+            if link_name
+                .is_some_and(|link_name| resolves_outside(outdir, Path::new(""), &link_name))
+            {
+                violations.push(ViolationKind::HardlinkOutside);
+            }
+        }
+        else if entry_type.is_symlink()
+        {
+            let base = relative.parent().unwrap_or(Path::new(""));
+            if resolves_outside(outdir, base, &link_name)
+            {
+                violations.push(ViolationKind::SymlinkOutside);
+            }
+        }
+    }
+    if entry.header().mode().is_ok_and(|mode| mode & SPECIAL_MODE_BITS != 0)
+    {
+        violations.push(ViolationKind::SpecialModeBits);
+    }
+    Ok(violations)
+}
+
+/// Drops the root and the first `n` components of `path`. Returns `None` if
+/// nothing is left.
+fn strip_leading(path: &Path, n: usize) -> Option<PathBuf>
+{
+    let stripped: PathBuf = path
+        .components()
+        .filter(|c| !matches!(c, Component::Prefix(_) | Component::RootDir | Component::CurDir))
+        .skip(n)
+        .collect();
+    (!stripped.as_os_str().is_empty()).then_some(stripped)
+}
+
+/// Returns the number of components of the deepest ancestor of `path`,
+/// including `path` itself, that matches one of `patterns`.
+fn match_depth(path: &Path, patterns: &[Pattern]) -> Option<usize>
+{
+    let options = MatchOptions { require_literal_separator: true, ..Default::default() };
+    path.ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .find(|ancestor| patterns.iter().any(|p| p.matches_path_with(ancestor, options)))
+        .map(|ancestor| ancestor.components().count())
+}
+
+/// Whether an entry at `path` is extracted. Like `crate::cli::RoastArgs`,
+/// excluding a directory excludes its contents and an included path
+/// overrides an excluded parent directory. If the include and exclude
+/// patterns match equally, the entry is excluded.
+fn is_selected(path: &Path, extract_options: &ExtractOptions) -> bool
+{
+    let included = match_depth(path, &extract_options.include);
+    if !extract_options.include.is_empty() && included.is_none()
+    {
+        return false;
+    }
+    match match_depth(path, &extract_options.exclude)
+    {
+        Some(excluded) => included.is_some_and(|included| included > excluded),
+        None => true,
+    }
+}
+
+/// Extracts `entry` to `relative` inside `outdir`. Without stripped
+/// components, this is `tar::Entry::unpack_in()`. Otherwise the checks of
+/// `unpack_in()` are done here since it only knows the path in the tarball.
+fn unpack_entry<R: Read>(
+    entry: &mut tar::Entry<R>,
+    outdir: &Path,
+    relative: &Path,
+    strip_components: usize,
+) -> io::Result<()>
+{
+    let is_hard_link = entry.header().entry_type().is_hard_link();
+    let link_src = match is_hard_link
+    {
+        true => entry
+            .link_name()?
+            .and_then(|link_name| strip_leading(&link_name, strip_components))
+            .map(|link_name| outdir.join(link_name)),
+        false => None,
+    };
+    // e.g. the target was filtered out or has too few components.
+    if is_hard_link && link_src.as_deref().is_none_or(|src| src.symlink_metadata().is_err())
+    {
+        warn!("⚠️ Skipping hardlink `{}` since its target is not extracted", relative.display());
+        return Ok(());
+    }
+    if strip_components == 0
+    {
+        entry.unpack_in(outdir)?;
+        return Ok(());
+    }
+    // Tar skips these as well.
+    if relative.components().any(|c| c == Component::ParentDir)
+    {
+        return Ok(());
+    }
+    let dst = outdir.join(relative);
+    let is_inside_outdir = |path: &Path| path.canonicalize().is_ok_and(|p| p.starts_with(outdir));
+    if let Some(parent) = dst.parent()
+    {
+        fs::create_dir_all(parent)?;
+        if !is_inside_outdir(parent)
+        {
+            let msg =
+                format!("Refusing to extract `{}` outside of the output directory", dst.display());
+            error!(msg);
+            return Err(io::Error::other(msg));
+        }
+    }
+    if let Some(link_src) = link_src
+    {
+        if !is_inside_outdir(&link_src)
+        {
+            let msg = format!(
+                "Hardlink target of `{}` is outside of the output directory",
+                relative.display()
+            );
+            error!(msg);
+            return Err(io::Error::other(msg));
+        }
+        return fs::hard_link(link_src, dst);
+    }
+    entry.unpack(&dst)?;
     Ok(())
 }
 
-/// Decompresses and extracts an archive with Zstd.
-pub fn tarzst(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
+/// Counts the bytes consumed from the compressed reader. The count is shared
+/// with the thread of `archive_as_tar()` for zip and 7z archives.
+struct CountingReader<R>
+{
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        let read = self.inner.read(buf)?;
+        self.count.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R>
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> { self.inner.fill_buf() }
+
+    fn consume(&mut self, amt: usize)
+    {
+        self.inner.consume(amt);
+        self.count.fetch_add(amt as u64, Ordering::Relaxed);
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<R>
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> { self.inner.seek(pos) }
+}
+
+/// Fails once the decompressed stream exceeds `max_ratio` times the
+/// compressed bytes consumed so far. Tar wraps the errors of its reader, so
+/// `exceeded` tells `unpack_entries()` that the failure came from here.
+struct RatioGuard<R>
+{
+    inner: R,
+    compressed: Arc<AtomicU64>,
+    decompressed: u64,
+    max_ratio: Option<u64>,
+    exceeded: Rc<Cell<bool>>,
+}
+
+impl<R: Read> Read for RatioGuard<R>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    {
+        let read = self.inner.read(buf)?;
+        self.decompressed += read as u64;
+        if let Some(max) = self.max_ratio
+            && self.decompressed > RATIO_CHECK_THRESHOLD
+            && self.decompressed > self.compressed.load(Ordering::Relaxed).saturating_mul(max)
+        {
+            self.exceeded.set(true);
+            return Err(LimitExceeded { limit: Limit::Ratio, max }.into());
+        }
+        Ok(read)
+    }
+}
+
+/// Returns the number of bytes an entry takes once extracted. Sparse files
+/// take more than the data stored in the tarball.
+fn unpacked_size<R: Read>(entry: &tar::Entry<R>) -> u64
+{
+    let sparse_size = entry
+        .header()
+        .as_gnu()
+        .filter(|_| entry.header().entry_type().is_gnu_sparse())
+        .and_then(|gnu| gnu.real_size().ok());
+    sparse_size.unwrap_or_default().max(entry.size())
+}
+
+/// Extracts `ar` to `outdir`. In safe mode, unsafe entries are rejected or
+/// sanitized. Like `tar::Archive::unpack()`, directories are extracted last so
+/// that their permissions do not prevent extracting their contents.
+fn unpack_entries<R: Read>(
+    ar: &mut tar::Archive<R>,
+    outdir: &Path,
+    extract_options: &ExtractOptions,
+) -> io::Result<Vec<Violation>>
+{
+    fs::create_dir_all(outdir)?;
+    let outdir = &outdir.canonicalize().unwrap_or(outdir.to_path_buf());
+    if extract_options.safe
+    {
+        // This masks the special mode bits.
+        ar.set_preserve_permissions(false);
+        ar.set_preserve_ownerships(false);
+    }
+    let strip_components = extract_options.strip_components;
+    let mut violations: Vec<Violation> = Vec::new();
+    let mut directories = Vec::new();
+    let mut entries: u64 = 0;
+    let mut total_size: u64 = 0;
+    for entry in ar.entries()?
+    {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let Some(relative) = strip_leading(&path, strip_components)
+        else
+        {
+            continue;
+        };
+        if !is_selected(&relative, extract_options)
+        {
+            trace!(?path, "Skipping unselected entry");
+            continue;
+        }
+        entries += 1;
+        if let Some(max) = extract_options.max_entries
+            && entries > max
+        {
+            return Err(LimitExceeded { limit: Limit::Entries, max }.into());
+        }
+        let size = unpacked_size(&entry);
+        if let Some(max) = extract_options.max_file_size
+            && size > max
+        {
+            error!(?path, size, "Entry is too large");
+            return Err(LimitExceeded { limit: Limit::FileSize, max }.into());
+        }
+        total_size = total_size.saturating_add(size);
+        if let Some(max) = extract_options.max_total_size
+            && total_size > max
+        {
+            return Err(LimitExceeded { limit: Limit::TotalSize, max }.into());
+        }
+        if extract_options.safe
+        {
+            let kinds = check_entry(&entry, outdir, &relative, strip_components)?;
+            let is_rejected = kinds.iter().any(ViolationKind::is_rejected);
+            for kind in kinds
+            {
+                let violation = Violation { path: path.clone(), kind };
+                warn!("⚠️ {}", violation);
+                violations.push(violation);
+            }
+            if is_rejected
+            {
+                continue;
+            }
+        }
+        if entry.header().entry_type() == tar::EntryType::Directory
+        {
+            directories.push((entry, relative));
+        }
+        else
+        {
+            unpack_entry(&mut entry, outdir, &relative, strip_components)?;
+        }
+    }
+    directories.sort_by(|(_, a), (_, b)| b.cmp(a));
+    for (mut dir, relative) in directories
+    {
+        unpack_entry(&mut dir, outdir, &relative, strip_components)?;
+    }
+    Ok(violations)
+}
+
+/// Decompresses a tarball compressed with `compression` from any `reader` and
+/// extracts it to `outdir`. Returns the entries that were rejected or
+/// sanitized in safe mode. Fails with `LimitExceeded` as soon as one of the
+/// limits of `extract_options` is exceeded.
+pub fn unpack_from(
+    reader: impl BufRead,
+    compression: Compression,
+    outdir: impl AsRef<Path>,
+    options: &CodecOptions,
+    extract_options: &ExtractOptions,
+) -> io::Result<Vec<Violation>>
+{
+    let compressed = Arc::new(AtomicU64::new(0));
+    let reader = CountingReader { inner: reader, count: Arc::clone(&compressed) };
+    unpack_counted(
+        compression.decoder(reader, options)?,
+        compressed,
+        outdir.as_ref(),
+        extract_options,
+    )
+}
+
+/// Extracts the tarball read from `decoder` to `outdir`. `compressed` is the
+/// number of bytes read from the source so far which the expansion ratio is
+/// measured against.
+fn unpack_counted(
+    decoder: impl Read,
+    compressed: Arc<AtomicU64>,
+    outdir: &Path,
+    extract_options: &ExtractOptions,
+) -> io::Result<Vec<Violation>>
+{
+    let exceeded = Rc::new(Cell::new(false));
+    let decoder = RatioGuard {
+        inner: decoder,
+        compressed,
+        decompressed: 0,
+        max_ratio: extract_options.max_ratio,
+        exceeded: Rc::clone(&exceeded),
+    };
+    let mut ar = tar::Archive::new(decoder);
+    unpack_entries(&mut ar, outdir, extract_options).map_err(|err| {
+        match (exceeded.get(), extract_options.max_ratio)
+        {
+            (true, Some(max)) => LimitExceeded { limit: Limit::Ratio, max }.into(),
+            _ => err,
+        }
+    })
+}
+
+/// Decompresses a tarball compressed with `compression` at `srcpath` and
+/// extracts it to `outdir`. Returns the entries that were rejected or
+/// sanitized in safe mode.
+pub fn untar(
+    compression: Compression,
+    outdir: impl AsRef<Path>,
+    srcpath: impl AsRef<Path>,
+    options: &CodecOptions,
+    extract_options: &ExtractOptions,
+) -> io::Result<Vec<Violation>>
 {
-    use zstd::Decoder;
-    let mut src = io::BufReader::new(fs::File::open(srcpath.as_ref())?);
-    src.seek(io::SeekFrom::Start(0))?;
-    let enc = Decoder::new(src)?;
-    let mut ar = tar::Archive::new(enc);
-    ar.unpack(outdir.as_ref())?;
+    let src = io::BufReader::new(fs::File::open(srcpath.as_ref())?);
+    let violations = unpack_from(src, compression, outdir.as_ref(), options, extract_options)?;
     debug!(
-        "Successfully decompressed and extracted tape zstd-compressed archive from {} to {}",
+        "Successfully decompressed and extracted tape {} archive from {} to {}",
+        compression,
         srcpath.as_ref().to_string_lossy(),
         outdir.as_ref().to_string_lossy(),
     );
+    Ok(violations)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Metadata of an entry in a tarball. See `list_from()`.
+pub struct EntryInfo
+{
+    pub path: PathBuf,
+    pub entry_type: tar::EntryType,
+    /// Target of symlinks and hardlinks.
+    pub link_name: Option<PathBuf>,
+    /// Size in bytes once extracted.
+    pub size: u64,
+    pub mode: u32,
+    /// Modification time in seconds since the Unix epoch.
+    pub mtime: u64,
+    pub uid: u64,
+    pub gid: u64,
+    pub username: Option<String>,
+    pub groupname: Option<String>,
+}
+
+impl EntryInfo
+{
+    fn from_entry<R: Read>(entry: &tar::Entry<R>) -> io::Result<Self>
+    {
+        let header = entry.header();
+        Ok(EntryInfo {
+            path: entry.path()?.into_owned(),
+            entry_type: header.entry_type(),
+            link_name: entry.link_name()?.map(|link_name| link_name.into_owned()),
+            size: unpacked_size(entry),
+            // Some archivers leave numeric fields empty.
+            mode: header.mode().unwrap_or_default(),
+            mtime: header.mtime().unwrap_or_default(),
+            uid: header.uid().unwrap_or_default(),
+            gid: header.gid().unwrap_or_default(),
+            username: header.username().ok().flatten().map(str::to_string),
+            groupname: header.groupname().ok().flatten().map(str::to_string),
+        })
+    }
+
+    /// Short name of the entry type e.g. `file` or `symlink`.
+    pub fn type_name(&self) -> &'static str
+    {
+        match self.entry_type
+        {
+            tar::EntryType::Regular | tar::EntryType::Continuous => "file",
+            tar::EntryType::Directory => "dir",
+            tar::EntryType::Symlink => "symlink",
+            tar::EntryType::Link => "hardlink",
+            tar::EntryType::Char => "char",
+            tar::EntryType::Block => "block",
+            tar::EntryType::Fifo => "fifo",
+            tar::EntryType::GNUSparse => "sparse",
+            _ => "other",
+        }
+    }
+}
+
+/// Decompresses a tarball compressed with `compression` from any `reader` and
+/// calls `on_entry` for each entry without extracting anything. The contents
+/// of the entries are skipped.
+pub fn list_from(
+    reader: impl BufRead,
+    compression: Compression,
+    options: &CodecOptions,
+    mut on_entry: impl FnMut(EntryInfo) -> io::Result<()>,
+) -> io::Result<()>
+{
+    let decoder = compression.decoder(reader, options)?;
+    let mut ar = tar::Archive::new(decoder);
+    for entry in ar.entries()?
+    {
+        on_entry(EntryInfo::from_entry(&entry?)?)?;
+    }
     Ok(())
 }
 
-/// Decompresses and extracts an archive with Xz/Lzma.
+/// Decompresses a tarball compressed with `compression` at `srcpath` and
+/// calls `on_entry` for each entry. See `list_from()`.
+pub fn list(
+    compression: Compression,
+    srcpath: impl AsRef<Path>,
+    options: &CodecOptions,
+    on_entry: impl FnMut(EntryInfo) -> io::Result<()>,
+) -> io::Result<()>
+{
+    let src = io::BufReader::new(fs::File::open(srcpath.as_ref())?);
+    list_from(src, compression, options, on_entry)
+}
+
+/// Decompresses a tarball compressed with `compression` from any `reader` and
+/// writes the contents of the only regular file matching `pattern` to
+/// `writer`. Returns the path of that file. If `pattern` is an exact path,
+/// decompression stops at the first match. Otherwise the rest of the tarball
+/// is read to make sure that no other file matches. Fails with
+/// `crate::error::Error::Filter` if no file or multiple files match.
+pub fn cat_from(
+    reader: impl BufRead,
+    compression: Compression,
+    options: &CodecOptions,
+    pattern: &Pattern,
+    writer: &mut impl Write,
+) -> io::Result<PathBuf>
+{
+    let is_exact = Pattern::escape(pattern.as_str()) == pattern.as_str();
+    let match_options = MatchOptions { require_literal_separator: true, ..Default::default() };
+    let decoder = compression.decoder(reader, options)?;
+    let mut ar = tar::Archive::new(decoder);
+    let mut matched: Option<(PathBuf, tempfile::SpooledTempFile)> = None;
+    for entry in ar.entries()?
+    {
+        let mut entry = entry?;
+        if !matches!(
+            entry.header().entry_type(),
+            tar::EntryType::Regular | tar::EntryType::Continuous
+        )
+        {
+            continue;
+        }
+        let Some(path) = strip_leading(&entry.path()?, 0)
+        else
+        {
+            continue;
+        };
+        if !pattern.matches_path_with(&path, match_options)
+        {
+            continue;
+        }
+        if is_exact
+        {
+            io::copy(&mut entry, writer)?;
+            return Ok(path);
+        }
+        if let Some((first, _)) = &matched
+        {
+            let msg = format!(
+                "Multiple files match `{}` e.g. `{}` and `{}`",
+                pattern,
+                first.display(),
+                path.display()
+            );
+            error!(msg);
+            return Err(Error::filter(msg).into());
+        }
+        let mut spool = tempfile::spooled_tempfile(CAT_SPOOL_SIZE);
+        io::copy(&mut entry, &mut spool)?;
+        matched = Some((path, spool));
+    }
+    let Some((path, mut spool)) = matched
+    else
+    {
+        let msg = format!("No file matches `{}`", pattern);
+        error!(msg);
+        return Err(Error::filter(msg).into());
+    };
+    spool.seek(SeekFrom::Start(0))?;
+    io::copy(&mut spool, writer)?;
+    Ok(path)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConvertedKind
+{
+    File,
+    Dir,
+    Symlink,
+}
+
+/// An entry of a zip or 7z archive that is about to be written as a tar entry.
+struct ConvertedEntry
+{
+    name: String,
+    kind: ConvertedKind,
+    mode: u32,
+    /// Modification time in seconds since the Unix epoch.
+    mtime: u64,
+    size: u64,
+}
+
+fn sevenz_error(err: sevenz_rust::Error) -> io::Error
+{
+    match err
+    {
+        sevenz_rust::Error::Io(err, _) | sevenz_rust::Error::FileOpen(err, _) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
+
+/// Seconds since the Unix epoch of a date in the proleptic Gregorian calendar.
+/// Dates before the epoch are clamped to it.
+fn unix_time(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> u64
+{
+    // See <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds =
+        days * 86_400 + i64::from(hour) * 3_600 + i64::from(minute) * 60 + i64::from(second);
+    u64::try_from(seconds).unwrap_or_default()
+}
+
+/// Appends `entry` with its contents read from `data` to `builder`. Entries
+/// with a `..` component cannot be represented in a tarball so they are
+/// skipped. Absolute paths are made relative like tar does. Both are added to
+/// `violations` regardless of safe mode since the path is lost afterwards.
+fn append_converted<W: Write>(
+    builder: &mut tar::Builder<W>,
+    entry: &ConvertedEntry,
+    data: &mut dyn Read,
+    violations: &mut Vec<Violation>,
+) -> io::Result<()>
+{
+    let original = Path::new(&entry.name);
+    if let Some(kind) = path_violation(original)
+    {
+        let violation = Violation { path: original.to_path_buf(), kind };
+        warn!("⚠️ {}", violation);
+        violations.push(violation);
+        if kind.is_rejected()
+        {
+            return Ok(());
+        }
+    }
+    let Some(path) = strip_leading(original, 0)
+    else
+    {
+        return Ok(());
+    };
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(entry.mode & 0o7777);
+    header.set_mtime(entry.mtime);
+    match entry.kind
+    {
+        ConvertedKind::Symlink =>
+        {
+            // The target of a symlink is stored as its contents.
+            let mut target = String::new();
+            data.read_to_string(&mut target)?;
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, path, target)
+        }
+        ConvertedKind::Dir =>
+        {
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_size(0);
+            builder.append_data(&mut header, path, io::empty())
+        }
+        ConvertedKind::File =>
+        {
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_size(entry.size);
+            builder.append_data(&mut header, path, data)
+        }
+    }
+}
+
+fn zip_to_tar<W: Write>(
+    source: impl Read + Seek,
+    builder: &mut tar::Builder<W>,
+    violations: &mut Vec<Violation>,
+) -> io::Result<()>
+{
+    let mut archive = zip::ZipArchive::new(source)?;
+    for index in 0..archive.len()
+    {
+        let mut file = archive.by_index(index)?;
+        let kind = if file.is_symlink()
+        {
+            ConvertedKind::Symlink
+        }
+        else if file.is_dir()
+        {
+            ConvertedKind::Dir
+        }
+        else
+        {
+            ConvertedKind::File
+        };
+        // Archives created on Windows have no permissions.
+        let default_mode = if kind == ConvertedKind::Dir { 0o755 } else { 0o644 };
+        let mtime = file.last_modified().map_or(0, |datetime| {
+            unix_time(
+                datetime.year(),
+                datetime.month(),
+                datetime.day(),
+                datetime.hour(),
+                datetime.minute(),
+                datetime.second(),
+            )
+        });
+        let entry = ConvertedEntry {
+            name: file.name().to_string(),
+            kind,
+            mode: file.unix_mode().unwrap_or(default_mode),
+            mtime,
+            size: file.size(),
+        };
+        append_converted(builder, &entry, &mut file, violations)?;
+    }
+    Ok(())
+}
+
+fn sevenz_to_tar<W: Write>(
+    source: impl Read + Seek,
+    len: u64,
+    builder: &mut tar::Builder<W>,
+    violations: &mut Vec<Violation>,
+) -> io::Result<()>
+{
+    let mut archive = sevenz_rust::SevenZReader::new(source, len, sevenz_rust::Password::empty())
+        .map_err(sevenz_error)?;
+    archive
+        .for_each_entries(&mut |file: &sevenz_rust::SevenZArchiveEntry, data: &mut dyn Read| {
+            // Anti-items mark deletions in incremental backups.
+            if file.is_anti_item()
+            {
+                return Ok(true);
+            }
+            // Archivers on Unix store the mode in the high 16 bits of the attributes.
+            let mode = if file.has_windows_attributes && file.windows_attributes() & 0x8000 != 0
+            {
+                Some(file.windows_attributes() >> 16)
+            }
+            else
+            {
+                None
+            };
+            let kind = if mode.is_some_and(|mode| mode & 0o170000 == 0o120000)
+            {
+                ConvertedKind::Symlink
+            }
+            else if file.is_directory()
+            {
+                ConvertedKind::Dir
+            }
+            else
+            {
+                ConvertedKind::File
+            };
+            let default_mode = if kind == ConvertedKind::Dir { 0o755 } else { 0o644 };
+            let entry = ConvertedEntry {
+                name: file.name().to_string(),
+                kind,
+                mode: mode.unwrap_or(default_mode),
+                mtime: (file.last_modified_date().to_raw() / 10_000_000)
+                    .saturating_sub(WINDOWS_EPOCH_OFFSET),
+                size: file.size(),
+            };
+            append_converted(builder, &entry, data, violations)?;
+            Ok(true)
+        })
+        .map_err(sevenz_error)?;
+    Ok(())
+}
+
+/// Converts the zip or 7z archive at `srcpath` to an uncompressed tarball on
+/// the fly and passes it to `consume` e.g. to list it with `list_from()` and
+/// `crate::common::Compression::Not`. The conversion runs on another thread so
+/// that the archive is never held in memory. Also returns the entries with an
+/// absolute path or a `..` component. See `append_converted()`. Use
+/// `unpack_archive()` to extract the archive.
+pub fn archive_as_tar<T>(
+    format: ArchiveFormat,
+    srcpath: impl AsRef<Path>,
+    consume: impl FnOnce(&mut dyn BufRead) -> io::Result<T>,
+) -> io::Result<(T, Vec<Violation>)>
+{
+    convert_archive(format, srcpath.as_ref(), Arc::new(AtomicU64::new(0)), consume)
+}
+
+/// `archive_as_tar()` that adds the bytes read from `srcpath` to `source_read`.
+fn convert_archive<T>(
+    format: ArchiveFormat,
+    srcpath: &Path,
+    source_read: Arc<AtomicU64>,
+    consume: impl FnOnce(&mut dyn BufRead) -> io::Result<T>,
+) -> io::Result<(T, Vec<Violation>)>
+{
+    let file = fs::File::open(srcpath)?;
+    let len = file.metadata()?.len();
+    let source = CountingReader { inner: io::BufReader::new(file), count: source_read };
+    let (reader, writer) = io::pipe()?;
+    let converter = std::thread::spawn(move || -> io::Result<Vec<Violation>> {
+        let mut violations = Vec::new();
+        let mut builder = tar::Builder::new(io::BufWriter::new(writer));
+        match format
+        {
+            ArchiveFormat::Zip => zip_to_tar(source, &mut builder, &mut violations)?,
+            ArchiveFormat::SevenZ => sevenz_to_tar(source, len, &mut builder, &mut violations)?,
+        }
+        builder.into_inner()?.flush()?;
+        Ok(violations)
+    });
+    let mut reader = io::BufReader::new(reader);
+    let res = consume(&mut reader);
+    // Lets the converter fail with a broken pipe if `consume` stopped early.
+    drop(reader);
+    let converted = converter.join().map_err(|_| {
+        error!("Converting the {} archive panicked", format);
+        io::Error::other(format!("Converting the {} archive panicked", format))
+    })?;
+    match converted
+    {
+        Ok(violations) => res.map(|consumed| (consumed, violations)),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe =>
+        {
+            res.map(|consumed| (consumed, Vec::new()))
+        }
+        Err(err) =>
+        {
+            error!(?err, "Failed to convert the {} archive", format);
+            Err(err)
+        }
+    }
+}
+
+/// Converts the zip or 7z archive at `srcpath` with `archive_as_tar()` and
+/// extracts it to `outdir`. Unlike extracting the converted tarball with
+/// `unpack_from()`, the expansion ratio is measured against the bytes read
+/// from `srcpath`. Returns the entries that were rejected or sanitized.
+pub fn unpack_archive(
+    format: ArchiveFormat,
+    srcpath: impl AsRef<Path>,
+    outdir: impl AsRef<Path>,
+    extract_options: &ExtractOptions,
+) -> io::Result<Vec<Violation>>
+{
+    let source_read = Arc::new(AtomicU64::new(0));
+    let (unpacked, mut violations) =
+        convert_archive(format, srcpath.as_ref(), Arc::clone(&source_read), |tar| {
+            unpack_counted(tar, source_read, outdir.as_ref(), extract_options)
+        })?;
+    violations.extend(unpacked);
+    Ok(violations)
+}
+
+/// Decompresses and extracts an archive with Gz. Archives with multiple
+/// concatenated gzip members e.g. from `pigz` are read until the last member.
+pub fn targz(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
+{
+    untar(Compression::Gz, outdir, srcpath, &CodecOptions::default(), &ExtractOptions::default())?;
+    Ok(())
+}
+
+/// Decompresses and extracts an archive with Zstd. If the archive was
+/// compressed with a dictionary, the same `dictionary` must be passed.
+/// Archives with multiple concatenated frames are read until the last frame.
+pub fn tarzst(
+    outdir: impl AsRef<Path>,
+    srcpath: impl AsRef<Path>,
+    dictionary: Option<&[u8]>,
+) -> io::Result<()>
+{
+    let options = CodecOptions { dictionary, ..Default::default() };
+    untar(Compression::Zst, outdir, srcpath, &options, &ExtractOptions::default())?;
+    Ok(())
+}
+
+/// Decompresses and extracts an archive with Xz/Lzma. Archives with multiple
+/// concatenated xz streams are read until the last stream.
 pub fn tarxz(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
 {
-    use xz2::read::XzDecoder;
-    let mut src = io::BufReader::new(fs::File::open(srcpath.as_ref())?);
-    src.seek(io::SeekFrom::Start(0))?;
-    let enc = XzDecoder::new(src);
-    let mut ar = tar::Archive::new(enc);
-    ar.unpack(outdir.as_ref())?;
-    debug!(
-        "Successfully decompressed and extracted tape xz-compressed archive from {} to {}",
-        srcpath.as_ref().to_string_lossy(),
-        outdir.as_ref().to_string_lossy(),
-    );
+    untar(Compression::Xz, outdir, srcpath, &CodecOptions::default(), &ExtractOptions::default())?;
     Ok(())
 }
 
 /// Decompresses and extracts an archive with Bz2.
 pub fn tarbz2(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
 {
-    use bzip2::bufread::MultiBzDecoder;
+    untar(Compression::Bz2, outdir, srcpath, &CodecOptions::default(), &ExtractOptions::default())?;
+    Ok(())
+}
+
+/// Decompresses and extracts an archive with Lz4.
+pub fn tarlz4(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
+{
+    untar(Compression::Lz4, outdir, srcpath, &CodecOptions::default(), &ExtractOptions::default())?;
+    Ok(())
+}
 
-    let mut src = io::BufReader::new(fs::File::open(srcpath.as_ref())?);
-    src.seek(io::SeekFrom::Start(0))?;
-    let enc = MultiBzDecoder::new(src);
-    let mut ar = tar::Archive::new(enc);
-    ar.unpack(outdir.as_ref())?;
-    debug!(
-        "Successfully decompressed and extracted tape bz2-compressed archive from {} to {}",
-        srcpath.as_ref().to_string_lossy(),
-        outdir.as_ref().to_string_lossy(),
-    );
+/// Decompresses and extracts an archive with Lzip. Archives with multiple
+/// members are read until the last member.
+pub fn tarlz(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
+{
+    untar(Compression::Lz, outdir, srcpath, &CodecOptions::default(), &ExtractOptions::default())?;
+    Ok(())
+}
+
+/// Decompresses and extracts an archive with legacy Lzma i.e. `lzma-alone`.
+pub fn tarlzma(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
+{
+    untar(
+        Compression::Lzma,
+        outdir,
+        srcpath,
+        &CodecOptions::default(),
+        &ExtractOptions::default(),
+    )?;
+    Ok(())
+}
+
+/// Decompresses and extracts an archive with Brotli.
+pub fn tarbr(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
+{
+    untar(Compression::Br, outdir, srcpath, &CodecOptions::default(), &ExtractOptions::default())?;
     Ok(())
 }
 
 /// Extracts an uncompressed archive.
 pub fn vanilla(outdir: impl AsRef<Path>, srcpath: impl AsRef<Path>) -> io::Result<()>
 {
-    let mut src = io::BufReader::new(fs::File::open(srcpath.as_ref())?);
-    src.seek(io::SeekFrom::Start(0))?;
-    let mut ar = tar::Archive::new(src);
-    ar.unpack(outdir.as_ref())?;
-    debug!(
-        "Successfully extracted tape archive from {} to {}",
-        srcpath.as_ref().to_string_lossy(),
-        outdir.as_ref().to_string_lossy(),
-    );
+    untar(Compression::Not, outdir, srcpath, &CodecOptions::default(), &ExtractOptions::default())?;
     Ok(())
 }
@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: MPL-2.0
+
+// Copyright (C) 2025 Soc Virnyl Estela and contributors
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! The error type returned by the operations in `crate::operations`.
+//!
+//! Most internal functions return `std::io::Result` since they mostly do
+//! I/O. Other failures are wrapped into an `io::Error` with `Error::into()`
+//! and are unwrapped again once they reach the operations. Git and regex
+//! errors wrapped with `io::Error::other()` and `LimitExceeded` converted with
+//! `io::Error::from()` are unwrapped as well.
+
+use crate::{
+    common::UnsupportedFormat,
+    decompress::LimitExceeded,
+};
+use std::{
+    fmt::{
+        self,
+        Display,
+    },
+    io,
+};
+
+/// Boxed source error of an `Error`.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Convenience alias of `std::result::Result` with `Error`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+/// Representation of the failures in libroast.
+pub enum Error
+{
+    /// Git operations failed e.g. cloning or finding a revision.
+    Git(git2::Error),
+    /// The archive format is not supported.
+    Format(UnsupportedFormat),
+    /// The archive exceeds one of the limits of
+    /// `crate::decompress::ExtractOptions`.
+    Limit(LimitExceeded),
+    /// Paths could not be resolved or filtered e.g. a glob without any match
+    /// or an include path outside the target directory.
+    Filter
+    {
+        msg: String, source: Option<BoxError>
+    },
+    /// Invalid or missing arguments e.g. a missing target, an invalid
+    /// compression level or an invalid rename regex.
+    InvalidArgument
+    {
+        msg: String, source: Option<BoxError>
+    },
+    /// Any other I/O error e.g. a full disk or missing permissions.
+    Io(io::Error),
+}
+
+impl Error
+{
+    pub fn filter(msg: impl Into<String>) -> Self
+    {
+        Error::Filter { msg: msg.into(), source: None }
+    }
+
+    pub fn invalid_argument(msg: impl Into<String>) -> Self
+    {
+        Error::InvalidArgument { msg: msg.into(), source: None }
+    }
+}
+
+impl Display for Error
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Error::Git(err) => write!(f, "Git error: {}", err),
+            Error::Format(err) => write!(f, "{}", err),
+            Error::Limit(err) => write!(f, "{}", err),
+            Error::Filter { msg, .. } => write!(f, "Path filtering error: {}", msg),
+            Error::InvalidArgument { msg, .. } => write!(f, "Invalid argument: {}", msg),
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        match self
+        {
+            Error::Git(err) => Some(err),
+            Error::Format(err) => Some(err),
+            Error::Limit(err) => Some(err),
+            Error::Filter { source, .. } | Error::InvalidArgument { source, .. } =>
+            {
+                source.as_deref().map(|err| err as &(dyn std::error::Error + 'static))
+            }
+            Error::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<git2::Error> for Error
+{
+    fn from(err: git2::Error) -> Self { Error::Git(err) }
+}
+
+impl From<UnsupportedFormat> for Error
+{
+    fn from(err: UnsupportedFormat) -> Self { Error::Format(err) }
+}
+
+impl From<LimitExceeded> for Error
+{
+    fn from(err: LimitExceeded) -> Self { Error::Limit(err) }
+}
+
+impl From<regex::Error> for Error
+{
+    fn from(err: regex::Error) -> Self
+    {
+        Error::InvalidArgument { msg: err.to_string(), source: Some(Box::new(err)) }
+    }
+}
+
+impl From<io::Error> for Error
+{
+    fn from(err: io::Error) -> Self
+    {
+        let is_wrapped = err.get_ref().is_some_and(|inner| {
+            inner.is::<Error>()
+                || inner.is::<git2::Error>()
+                || inner.is::<regex::Error>()
+                || inner.is::<UnsupportedFormat>()
+                || inner.is::<LimitExceeded>()
+        });
+        if !is_wrapped
+        {
+            return Error::Io(err);
+        }
+        let kind = err.kind();
+        let Some(inner) = err.into_inner()
+        else
+        {
+            return Error::Io(io::Error::from(kind));
+        };
+        let inner = match inner.downcast::<Error>()
+        {
+            Ok(err) => return *err,
+            Err(inner) => inner,
+        };
+        let inner = match inner.downcast::<git2::Error>()
+        {
+            Ok(err) => return Error::Git(*err),
+            Err(inner) => inner,
+        };
+        let inner = match inner.downcast::<regex::Error>()
+        {
+            Ok(err) => return Error::from(*err),
+            Err(inner) => inner,
+        };
+        let inner = match inner.downcast::<UnsupportedFormat>()
+        {
+            Ok(err) => return Error::Format(*err),
+            Err(inner) => inner,
+        };
+        match inner.downcast::<LimitExceeded>()
+        {
+            Ok(err) => Error::Limit(*err),
+            Err(inner) => Error::Io(io::Error::new(kind, inner)),
+        }
+    }
+}
+
+impl From<Error> for io::Error
+{
+    fn from(err: Error) -> Self
+    {
+        match err
+        {
+            Error::Io(err) => err,
+            err @ Error::Git(_) => io::Error::other(err),
+            err @ Error::Format(_) => io::Error::new(io::ErrorKind::Unsupported, err),
+            err @ Error::Limit(_) => io::Error::new(io::ErrorKind::InvalidData, err),
+            err @ Error::Filter { .. } => io::Error::new(io::ErrorKind::NotFound, err),
+            err @ Error::InvalidArgument { .. } => io::Error::new(io::ErrorKind::InvalidInput, err),
+        }
+    }
+}
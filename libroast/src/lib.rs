@@ -6,9 +6,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub mod codec;
 pub mod common;
 pub mod compress;
 pub mod consts;
 pub mod decompress;
+pub mod error;
+pub mod manifest;
 pub mod operations;
 pub mod utils;
+
+pub use error::Error;
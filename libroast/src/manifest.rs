@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: MPL-2.0
+
+// Copyright (C) 2025 Soc Virnyl Estela and contributors
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! An in-memory plan of an archive. Each entry maps a source path to its path
+//! in the archive. The builders in `crate::compress` stream the entries
+//! straight from their sources so nothing has to be copied beforehand.
+
+use crate::utils::canonicalize_entry;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+#[allow(unused_imports)]
+use tracing::{
+    debug,
+    error,
+    info,
+    trace,
+    warn,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What an entry is archived as. A `File` or `Dir` entry whose source is a
+/// symlink is archived as the file or directory it points to.
+pub enum EntryKind
+{
+    File,
+    Dir,
+    Symlink,
+}
+
+impl EntryKind
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            EntryKind::File => "file",
+            EntryKind::Dir => "dir",
+            EntryKind::Symlink => "symlink",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The rule that put an entry in a `Manifest`. Shown by `roast --dry-run`.
+pub enum EntryRule
+{
+    /// Found while walking the target directory.
+    #[default]
+    Target,
+    /// The target directory itself with `--preserve-root`.
+    PreserveRoot,
+    /// The `--include` path or pattern that selected the entry.
+    Include(String),
+    /// The `--additional-paths` value that added the entry.
+    AdditionalPath(String),
+}
+
+impl EntryRule
+{
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            EntryRule::Target => "target",
+            EntryRule::PreserveRoot => "preserve-root",
+            EntryRule::Include(_) => "include",
+            EntryRule::AdditionalPath(_) => "additional-path",
+        }
+    }
+
+    /// The argument value of the rule if it came from one.
+    pub fn value(&self) -> Option<&str>
+    {
+        match self
+        {
+            EntryRule::Target | EntryRule::PreserveRoot => None,
+            EntryRule::Include(value) | EntryRule::AdditionalPath(value) => Some(value),
+        }
+    }
+}
+
+impl fmt::Display for EntryRule
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self.value()
+        {
+            Some(value) => write!(f, "{} {}", self.name(), value),
+            None => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A single entry of a `Manifest`.
+pub struct ManifestEntry
+{
+    /// Path the entry is read from.
+    pub source: PathBuf,
+    /// Relative path of the entry in the archive.
+    pub archive_path: PathBuf,
+    pub kind: EntryKind,
+    pub rule: EntryRule,
+}
+
+impl ManifestEntry
+{
+    pub fn new(
+        source: impl Into<PathBuf>,
+        archive_path: impl Into<PathBuf>,
+        kind: EntryKind,
+    ) -> Self
+    {
+        ManifestEntry {
+            source: source.into(),
+            archive_path: archive_path.into(),
+            kind,
+            rule: EntryRule::default(),
+        }
+    }
+
+    pub fn with_rule(self, rule: EntryRule) -> Self { ManifestEntry { rule, ..self } }
+
+    /// Metadata of the source. Symlinks are only followed if the entry is not a
+    /// `EntryKind::Symlink`.
+    pub fn metadata(&self) -> io::Result<fs::Metadata>
+    {
+        match self.kind
+        {
+            EntryKind::Symlink => self.source.symlink_metadata(),
+            EntryKind::File | EntryKind::Dir => self.source.metadata(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Entries of an archive keyed by their archive path. Inserting an entry with
+/// the same archive path replaces the previous one.
+pub struct Manifest
+{
+    entries: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+impl Manifest
+{
+    /// Plans the files of a directory that was already prepared e.g. with
+    /// `crate::utils::copy_dir_all()`. Each path in `archive_files` must be
+    /// within `target_dir`. Directories are kept as entries.
+    pub fn from_files(target_dir: &Path, archive_files: &[impl AsRef<Path>]) -> io::Result<Self>
+    {
+        let target_dir = target_dir.canonicalize().unwrap_or(target_dir.to_path_buf());
+        let mut manifest = Manifest::default();
+        for f in archive_files
+        {
+            let f = canonicalize_entry(f.as_ref(), false);
+            let Ok(metadata) = f.symlink_metadata()
+            else
+            {
+                error!("THIS IS A BUG. Unable to proceed. {} does not exist.", f.to_string_lossy());
+                return Err(io::Error::other(f.to_string_lossy()));
+            };
+            let kind = if metadata.is_symlink()
+            {
+                EntryKind::Symlink
+            }
+            else if metadata.is_file()
+            {
+                EntryKind::File
+            }
+            else if metadata.is_dir()
+            {
+                EntryKind::Dir
+            }
+            else
+            {
+                error!("Ignoring unexpected special file: {:?}", f);
+                continue;
+            };
+            let archive_path =
+                f.strip_prefix(&target_dir).map(Path::to_path_buf).map_err(|err| {
+                    error!(
+                        ?err,
+                        "THIS IS A BUG. Unable to proceed. {} is not within {}.",
+                        f.to_string_lossy(),
+                        target_dir.to_string_lossy()
+                    );
+                    io::Error::other(f.to_string_lossy())
+                })?;
+            manifest.insert(ManifestEntry::new(f, archive_path, kind));
+        }
+        Ok(manifest)
+    }
+
+    /// Adds `entry`. An entry with the same archive path is replaced. If the
+    /// walk of the target directory finds the source of the replaced entry
+    /// again, the rule of the replaced entry is kept e.g. for an included file.
+    pub fn insert(&mut self, mut entry: ManifestEntry)
+    {
+        if entry.rule == EntryRule::Target
+            && let Some(replaced) = self.entries.get(&entry.archive_path)
+            && replaced.source == entry.source
+        {
+            entry.rule = replaced.rule.clone();
+        }
+        trace!(?entry, "Planned entry");
+        self.entries.insert(entry.archive_path.clone(), entry);
+    }
+
+    /// Drops the directory entries that have other entries below them. Only
+    /// empty directories need an entry of their own since the others are
+    /// implied by their contents.
+    pub fn retain_empty_dirs(&mut self)
+    {
+        // Entries below a directory come right after it in the map.
+        let keys: Vec<&PathBuf> = self.entries.keys().collect();
+        let non_empty_dirs: Vec<PathBuf> = keys
+            .windows(2)
+            .filter(|pair| pair[1].starts_with(pair[0]))
+            .map(|pair| pair[0].to_path_buf())
+            .filter(|dir| self.entries.get(dir).is_some_and(|e| e.kind == EntryKind::Dir))
+            .collect();
+        for dir in non_empty_dirs
+        {
+            self.entries.remove(&dir);
+        }
+    }
+
+    /// Keeps only the entries for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&ManifestEntry) -> bool)
+    {
+        self.entries.retain(|_, entry| keep(entry));
+    }
+
+    /// The entries sorted by their archive path.
+    pub fn entries(&self) -> impl Iterator<Item = &ManifestEntry> { self.entries.values() }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+}
+
+impl Extend<ManifestEntry> for Manifest
+{
+    fn extend<T: IntoIterator<Item = ManifestEntry>>(&mut self, iter: T)
+    {
+        iter.into_iter().for_each(|entry| self.insert(entry));
+    }
+}
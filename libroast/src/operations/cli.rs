@@ -1,20 +1,29 @@
 //! Mostly structs that are used for `clap` for CLI arguments.
 //! Also useful for just anything else not CLI.
 
-use crate::common::Compression;
+use crate::common::{
+    Compression,
+    InputFormat,
+};
 use clap::{
     Command,
     Parser,
     Subcommand,
+    ValueEnum,
 };
 use clap_complete::{
     Generator,
     Shell,
     generate,
 };
+use hifitime::Epoch;
 use std::{
+    fs,
     io,
-    path::PathBuf,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 #[allow(unused_imports)]
 use tracing::{
@@ -31,6 +40,36 @@ pub(crate) fn print_completions<G: Generator>(generator: G, cmd: &mut Command)
     generate(generator, cmd, cmd.get_name().to_string(), &mut io::stdout());
 }
 
+pub(crate) fn write_zstd_dict(
+    samples: &[PathBuf],
+    max_size: usize,
+    outfile: &Path,
+) -> io::Result<()>
+{
+    let dictionary = crate::compress::train_zstd_dict(samples, max_size)?;
+    fs::write(outfile, dictionary)
+        .inspect_err(|err| error!(?err, "Unable to write dictionary to {}", outfile.display()))?;
+    info!("📖 Trained zstd dictionary is now in {}", outfile.display());
+    Ok(())
+}
+
+/// Parses the dates of `--exclude-older-than` and `--exclude-newer-than`.
+fn parse_date(value: &str) -> Result<Epoch, String>
+{
+    Epoch::from_gregorian_str(value).map_err(|err| format!("invalid date `{}`: {}", value, err))
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// Types of entries that `--exclude-type` skips.
+pub enum EntryType
+{
+    /// Regular files, including dereferenced symlinks.
+    Regular,
+    Symlink,
+    /// Directories that have nothing left in them after filtering.
+    EmptyDir,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Subcommands
 {
@@ -39,6 +78,18 @@ pub enum Subcommands
     {
         shell: Shell
     },
+    #[command(
+        about = "Train a zstd dictionary from sample files e.g. earlier uncompressed tarballs"
+    )]
+    TrainZstdDict
+    {
+        #[arg(long, short = 'o', help = "Output file of the trained dictionary.")]
+        outfile: PathBuf,
+        #[arg(long, default_value_t = 112640, help = "Maximum size of the dictionary in bytes.")]
+        max_size: usize,
+        #[arg(required = true, help = "Sample files to train the dictionary from.")]
+        samples: Vec<PathBuf>,
+    },
 }
 
 #[derive(Debug, Parser)]
@@ -68,15 +119,18 @@ pub struct RoastArgs
         help = "Additional paths such as files or directories in the target directory to include \
                 to the archive. Their parent directory will be put next to the target directory's \
                 work directory. The work directory is based on the preserve root option. This is \
-                different from `--additional_paths`. Useful to override excluded directories. ⚠️ \
-                Careful if the archive has whether preserved root set when it was created."
+                different from `--additional_paths`. Useful to override excluded directories. \
+                Accepts gitignore-style patterns e.g. `**/*.h`. ⚠️ Careful if the archive has \
+                whether preserved root set when it was created."
     )]
     pub include: Option<Vec<PathBuf>>,
     #[arg(
         long,
         short = 'E',
         help = "Additional paths such as files or directories from within target directory's work \
-                directory to exclude when generating the archive."
+                directory to exclude when generating the archive. Accepts gitignore-style \
+                patterns e.g. `**/*.o`, `target/` or `!keep.me`. Plain paths are relative to the \
+                target directory."
     )]
     pub exclude: Option<Vec<PathBuf>>,
     #[arg(
@@ -89,10 +143,25 @@ pub struct RoastArgs
                 it will be created."
     )]
     pub additional_paths: Option<Vec<String>>,
-    #[arg(long, short = 'f', help = "Output file of the generated archive with path.")]
+    #[arg(
+        long,
+        short = 'f',
+        help = "Output file of the generated archive with path. The archive format is based on \
+                the file extension e.g. `.tar.zst` or `.zip`. Pass `-` to write a tarball to \
+                stdout."
+    )]
     pub outfile: Option<PathBuf>,
     #[arg(long, short = 'd', help = "Output path of the generated archive.")]
     pub outdir: Option<PathBuf>,
+    #[arg(
+        long,
+        short = 'c',
+        help = "Compression to use when writing the archive to stdout with `--outfile -`. \
+                Defaults to zst. Otherwise, the compression is based on the file extension of \
+                `--outfile` e.g. `.tar.gz` or `.tgz`. If `--outfile` has no such extension, the \
+                extension of this compression is appended."
+    )]
+    pub compression: Option<Compression>,
     #[arg(
         long,
         short = 'p',
@@ -125,6 +194,79 @@ pub struct RoastArgs
         action = clap::ArgAction::Set
     )]
     pub ignore_hidden: bool,
+    #[arg(
+        long,
+        help = "Whether to follow symlinks and archive the files they point to. Symlinks are archived as symlinks by default.",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub dereference: bool,
+    #[arg(
+        long,
+        help = "Whether to skip the paths ignored by the `.gitignore`, `.ignore` and `.roastignore` files in the target directory. Later files take precedence over earlier ones in the same directory.",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub respect_ignore_files: bool,
+    #[arg(
+        long,
+        help = "Skip regular files of the target directory larger than this size in bytes."
+    )]
+    pub max_file_size: Option<u64>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Skip entries of the target directory of this type. Can be passed more than once."
+    )]
+    pub exclude_type: Option<Vec<EntryType>>,
+    #[arg(
+        long,
+        value_parser = parse_date,
+        help = "Skip files and symlinks of the target directory last modified before this date \
+                e.g. `2024-01-31` or `2024-01-31T12:00:00Z`. Dates without a timezone are in UTC."
+    )]
+    pub exclude_older_than: Option<Epoch>,
+    #[arg(
+        long,
+        value_parser = parse_date,
+        help = "Skip files and symlinks of the target directory last modified after this date \
+                e.g. `2024-01-31` or `2024-01-31T12:00:00Z`. Dates without a timezone are in UTC."
+    )]
+    pub exclude_newer_than: Option<Epoch>,
+    #[arg(
+        long,
+        short = 'l',
+        allow_negative_numbers = true,
+        help = "Compression level to use. Valid levels depend on the compression: gz, xz, lz, \
+                lzma and zip are from 0 to 9, bz2 is from 1 to 9, lz4 is from 1 to 12, br is from \
+                0 to 11 and zst is up to 22. Zst levels from 20 to 22 are ultra levels with long \
+                distance matching while negative zst levels are fast levels. Defaults to 6 for \
+                gz, xz, lz, lzma and zip, 9 for bz2, 1 for lz4, 11 for br and 19 for zst."
+    )]
+    pub level: Option<i32>,
+    #[arg(
+        long,
+        help = "Number of threads to use for zst and xz compression. Other compression options \
+                are single-threaded. Set to 0 to use the available CPUs, respecting cgroup CPU \
+                quotas.",
+        default_value_t = 0
+    )]
+    pub threads: u32,
+    #[arg(
+        long,
+        help = "Path to a zstd dictionary to compress with. Only used for zst. The same \
+                dictionary is required to decompress the tarball."
+    )]
+    pub zstd_dict: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Print the archive path, source path and rule of each planned entry to stdout \
+                instead of writing the archive. The rule is what put the entry there e.g. the \
+                `--include` pattern that matched it. Nothing is written to disk."
+    )]
+    pub dry_run: bool,
+    #[arg(long, requires = "dry_run", help = "Print the dry run as JSON, one object per line.")]
+    pub json: bool,
     #[arg(long, short = 'S', help = "Whether to silence the output or not.", default_value_t = true, action = clap::ArgAction::Set)]
     pub silent: bool,
     #[command(subcommand)]
@@ -148,11 +290,80 @@ pub struct RawArgs
     #[arg(
         long,
         short = 't',
-        help = "Target tarball file to extract and decompress. Supports globbing."
+        help = "Target tarball, zip or 7z file to extract and decompress. Supports globbing. Pass \
+                `-` to read a tarball from stdin."
     )]
     pub target: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Format of the target instead of detecting it from its magic bytes e.g. for a \
+                brotli tarball without a file extension."
+    )]
+    pub format: Option<InputFormat>,
     #[arg(long, short = 'd', help = "Output directory of extracted archive.")]
     pub outdir: Option<PathBuf>,
+    #[arg(
+        long,
+        short = 'l',
+        help = "List the entry type, mode, owner, size, modification time and path of each entry \
+                of the target tarball instead of extracting it."
+    )]
+    pub list: bool,
+    #[arg(long, requires = "list", help = "Print the listing as JSON, one object per line.")]
+    pub json: bool,
+    #[arg(
+        long,
+        conflicts_with = "list",
+        help = "Write the contents of the only regular file matching this path or glob pattern \
+                e.g. `*/Cargo.toml` to stdout instead of extracting the target tarball. Fails if \
+                no file or multiple files match."
+    )]
+    pub cat: Option<String>,
+    #[arg(
+        long,
+        short = 'i',
+        help = "Only extract entries matching these glob patterns e.g. `**/Cargo.lock`. Matching \
+                a directory extracts its contents. An include overrides an exclude of a parent \
+                directory. Patterns are matched after `--strip-components`."
+    )]
+    pub include: Option<Vec<String>>,
+    #[arg(
+        long,
+        short = 'E',
+        help = "Skip entries matching these glob patterns. Matching a directory skips its \
+                contents. Patterns are matched after `--strip-components`."
+    )]
+    pub exclude: Option<Vec<String>>,
+    #[arg(
+        long,
+        help = "Drop this number of leading path components when extracting like GNU tar. Entries \
+                with fewer components are skipped.",
+        default_value_t = 0
+    )]
+    pub strip_components: usize,
+    #[arg(long, help = "Path to the zstd dictionary that the target tarball was compressed with.")]
+    pub zstd_dict: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Whether to reject or sanitize unsafe entries of the tarball e.g. absolute paths, \
+                `..` components, links pointing outside the output directory, device nodes and \
+                setuid bits. Only disable this for trusted tarballs.",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    pub safe_extract: bool,
+    #[arg(long, help = "Maximum total size in bytes of the extracted entries.")]
+    pub max_total_size: Option<u64>,
+    #[arg(long, help = "Maximum number of entries to extract.")]
+    pub max_entries: Option<u64>,
+    #[arg(long, help = "Maximum size in bytes of a single extracted entry.")]
+    pub max_file_size: Option<u64>,
+    #[arg(
+        long,
+        help = "Maximum ratio of the decompressed size to the compressed size. Checked after the \
+                first MiB has been decompressed."
+    )]
+    pub max_ratio: Option<u64>,
     #[arg(long, short = 'S', help = "Whether to silence the output or not.", default_value_t = true, action = clap::ArgAction::Set)]
     pub silent: bool,
     #[command(subcommand)]
@@ -176,23 +387,31 @@ pub struct RecomprizzArgs
     #[arg(
         long,
         short = 't',
-        help = "Target tarball file to extract and recompress. Supports globbing."
+        help = "Target tarball, zip or 7z file to extract and recompress. Supports globbing."
     )]
     pub target: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Format of the target instead of detecting it from its magic bytes e.g. for a \
+                brotli tarball without a file extension."
+    )]
+    pub format: Option<InputFormat>,
     #[arg(
         long,
         short = 'i',
         help = "Additional paths such as files or directories in the target directory to include \
                 to the archive. Their parent directory will be put next to the target directory's \
                 work directory. The work directory is based on the preserve root option. This is \
-                different from `--additional_paths`. Useful to override excluded directories."
+                different from `--additional_paths`. Useful to override excluded directories. \
+                Accepts gitignore-style patterns e.g. `**/*.h`."
     )]
     pub include: Option<Vec<PathBuf>>,
     #[arg(
         long,
         short = 'E',
         help = "Additional paths such as files or directories from within target directory's work \
-                directory to exclude when generating the archive. ⚠️ Careful if the archive has \
+                directory to exclude when generating the archive. Accepts gitignore-style \
+                patterns e.g. `**/*.o`, `target/` or `!keep.me`. ⚠️ Careful if the archive has \
                 whether preserved root set when it was created."
     )]
     pub exclude: Option<Vec<PathBuf>>,
@@ -249,6 +468,73 @@ pub struct RecomprizzArgs
         action = clap::ArgAction::Set
     )]
     pub ignore_hidden: bool,
+    #[arg(
+        long,
+        help = "Whether to follow symlinks and archive the files they point to. Symlinks are archived as symlinks by default.",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub dereference: bool,
+    #[arg(
+        long,
+        help = "Whether to skip the paths ignored by the `.gitignore`, `.ignore` and `.roastignore` files in the target directory. Later files take precedence over earlier ones in the same directory.",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub respect_ignore_files: bool,
+    #[arg(
+        long,
+        short = 'l',
+        allow_negative_numbers = true,
+        help = "Compression level to use. Valid levels depend on the compression: gz, xz, lz and \
+                lzma are from 0 to 9, bz2 is from 1 to 9, lz4 is from 1 to 12, br is from 0 to 11 \
+                and zst is up to 22. Zst levels from 20 to 22 are ultra levels with long distance \
+                matching while negative zst levels are fast levels. Defaults to 6 for gz, xz, lz \
+                and lzma, 9 for bz2, 1 for lz4, 11 for br and 19 for zst."
+    )]
+    pub level: Option<i32>,
+    #[arg(
+        long,
+        help = "Number of threads to use for zst and xz compression. Other compression options \
+                are single-threaded. Set to 0 to use the available CPUs, respecting cgroup CPU \
+                quotas.",
+        default_value_t = 0
+    )]
+    pub threads: u32,
+    #[arg(
+        long,
+        help = "Path to a zstd dictionary to compress the output with. Only used for zst. The \
+                same dictionary is required to decompress the output tarball. See \
+                `--input-zstd-dict` for the target."
+    )]
+    pub zstd_dict: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Path to the zstd dictionary that the target tarball was compressed with. Only \
+                used to decompress the target. See `--zstd-dict` for the output."
+    )]
+    pub input_zstd_dict: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Whether to reject or sanitize unsafe entries of the tarball e.g. absolute paths, \
+                `..` components, links pointing outside the output directory, device nodes and \
+                setuid bits. Only disable this for trusted tarballs.",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    pub safe_extract: bool,
+    #[arg(long, help = "Maximum total size in bytes of the extracted entries.")]
+    pub max_total_size: Option<u64>,
+    #[arg(long, help = "Maximum number of entries to extract.")]
+    pub max_entries: Option<u64>,
+    #[arg(long, help = "Maximum size in bytes of a single extracted entry.")]
+    pub max_file_size: Option<u64>,
+    #[arg(
+        long,
+        help = "Maximum ratio of the decompressed size to the compressed size. Checked after the \
+                first MiB has been decompressed."
+    )]
+    pub max_ratio: Option<u64>,
     #[arg(long, short = 'S', help = "Whether to silence the output or not.", default_value_t = true, action = clap::ArgAction::Set)]
     pub silent: bool,
     #[command(subcommand)]
@@ -312,9 +598,18 @@ pub struct RoastScmArgs
         long,
         short = 'E',
         help = "Additional paths such as files or directories from within target repository's \
-                work directory to exclude when generating the archive."
+                work directory to exclude when generating the archive. Accepts gitignore-style \
+                patterns e.g. `**/*.o`, `target/` or `!keep.me`."
     )]
     pub exclude: Option<Vec<PathBuf>>,
+    #[arg(
+        long,
+        short = 'i',
+        help = "Paths such as files or directories from within target repository's work directory \
+                to include even if they are excluded. Accepts gitignore-style patterns e.g. \
+                `**/*.h`."
+    )]
+    pub include: Option<Vec<PathBuf>>,
     #[arg(
         long,
         help = "Revision or tag. It can also be a specific commit hash or branch. Supports <https://git-scm.com/docs/git-rev-parse.html#_specifying_revisions>."
@@ -373,8 +668,72 @@ pub struct RoastScmArgs
         action = clap::ArgAction::Set
     )]
     pub ignore_hidden: bool,
+    #[arg(
+        long,
+        help = "Whether to follow symlinks and archive the files they point to. Symlinks are archived as symlinks by default.",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub dereference: bool,
+    #[arg(
+        long,
+        help = "Whether to skip the paths ignored by the `.gitignore`, `.ignore` and `.roastignore` files in the target directory. Later files take precedence over earlier ones in the same directory.",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub respect_ignore_files: bool,
+    #[arg(
+        long,
+        help = "Skip regular files of the target directory larger than this size in bytes."
+    )]
+    pub max_file_size: Option<u64>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Skip entries of the target directory of this type. Can be passed more than once."
+    )]
+    pub exclude_type: Option<Vec<EntryType>>,
+    #[arg(
+        long,
+        value_parser = parse_date,
+        help = "Skip files and symlinks of the target directory last modified before this date \
+                e.g. `2024-01-31` or `2024-01-31T12:00:00Z`. Dates without a timezone are in UTC."
+    )]
+    pub exclude_older_than: Option<Epoch>,
+    #[arg(
+        long,
+        value_parser = parse_date,
+        help = "Skip files and symlinks of the target directory last modified after this date \
+                e.g. `2024-01-31` or `2024-01-31T12:00:00Z`. Dates without a timezone are in UTC."
+    )]
+    pub exclude_newer_than: Option<Epoch>,
     #[arg(long, short = 'c', help = "Compression to use.", default_value_t)]
     pub compression: Compression,
+    #[arg(
+        long,
+        short = 'l',
+        allow_negative_numbers = true,
+        help = "Compression level to use. Valid levels depend on the compression: gz, xz, lz and \
+                lzma are from 0 to 9, bz2 is from 1 to 9, lz4 is from 1 to 12, br is from 0 to 11 \
+                and zst is up to 22. Zst levels from 20 to 22 are ultra levels with long distance \
+                matching while negative zst levels are fast levels. Defaults to 6 for gz, xz, lz \
+                and lzma, 9 for bz2, 1 for lz4, 11 for br and 19 for zst."
+    )]
+    pub level: Option<i32>,
+    #[arg(
+        long,
+        help = "Number of threads to use for zst and xz compression. Other compression options \
+                are single-threaded. Set to 0 to use the available CPUs, respecting cgroup CPU \
+                quotas.",
+        default_value_t = 0
+    )]
+    pub threads: u32,
+    #[arg(
+        long,
+        help = "Path to a zstd dictionary to compress with. Only used for zst. The same \
+                dictionary is required to decompress the tarball."
+    )]
+    pub zstd_dict: Option<PathBuf>,
     #[arg(long, short = 'S', help = "Whether to silence the output or not.", default_value_t = true, action = clap::ArgAction::Set)]
     pub silent: bool,
     #[command(subcommand)]
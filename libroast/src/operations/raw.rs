@@ -7,19 +7,52 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
-    decompress,
+    codec::CodecOptions,
+    common::{
+        ArchiveFormat,
+        Compression,
+        SupportedFormat,
+    },
+    decompress::{
+        self,
+        EntryInfo,
+        ExtractOptions,
+        Violation,
+    },
+    error::{
+        Error,
+        Result,
+    },
     operations::cli::{
         RawArgs,
         print_completions,
+        write_zstd_dict,
     },
     utils::{
+        MAGIC_PEEK_SIZE,
+        detect_compression,
         is_supported_format,
+        json_string,
         process_globs,
         start_tracing,
     },
 };
 use clap::CommandFactory;
-use std::io;
+use glob::Pattern;
+use hifitime::Epoch;
+use std::{
+    fs,
+    io::{
+        self,
+        BufRead,
+        Read,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
 #[allow(unused_imports)]
 use tracing::{
     Level,
@@ -30,9 +63,257 @@ use tracing::{
     warn,
 };
 
+fn read_zstd_dict(zstd_dict: Option<&Path>) -> io::Result<Option<Vec<u8>>>
+{
+    zstd_dict
+        .map(|dict| {
+            fs::read(dict).inspect_err(|err| {
+                error!(?err, "Unable to read zstd dictionary");
+            })
+        })
+        .transpose()
+}
+
+fn parse_patterns(patterns: Option<&[String]>) -> Result<Vec<Pattern>>
+{
+    patterns
+        .unwrap_or_default()
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern).map_err(|err| {
+                error!(?err, pattern, "Invalid glob pattern");
+                Error::InvalidArgument {
+                    msg: format!("Invalid glob pattern `{}`", pattern),
+                    source: Some(Box::new(err)),
+                }
+            })
+        })
+        .collect()
+}
+
+fn extract_options(raw_args: &RawArgs) -> Result<ExtractOptions>
+{
+    Ok(ExtractOptions {
+        safe: raw_args.safe_extract,
+        max_total_size: raw_args.max_total_size,
+        max_entries: raw_args.max_entries,
+        max_file_size: raw_args.max_file_size,
+        max_ratio: raw_args.max_ratio,
+        include: parse_patterns(raw_args.include.as_deref())?,
+        exclude: parse_patterns(raw_args.exclude.as_deref())?,
+        strip_components: raw_args.strip_components,
+    })
+}
+
+/// Reports the entries that were rejected or sanitized during extraction.
+fn report_violations(violations: &[Violation])
+{
+    for violation in violations
+    {
+        eprintln!("⚠️ {}", violation);
+    }
+    if !violations.is_empty()
+    {
+        warn!("⚠️ Extraction has {} unsafe entries. See the output above.", violations.len());
+    }
+}
+
+/// Reports extraction errors even if the output is silenced e.g. an exceeded
+/// limit.
+fn report_error(err: &Error)
+{
+    eprintln!("{}", err);
+    error!(?err);
+}
+
+/// Formats an entry for `--list` like `tar -tv` or as a JSON object.
+fn format_entry(entry: &EntryInfo, json: bool) -> String
+{
+    let owner = match (&entry.username, &entry.groupname)
+    {
+        (Some(username), Some(groupname)) if !username.is_empty() && !groupname.is_empty() =>
+        {
+            format!("{}/{}", username, groupname)
+        }
+        _ => format!("{}/{}", entry.uid, entry.gid),
+    };
+    let path = entry.path.to_string_lossy();
+    let link_name = entry.link_name.as_deref().map(Path::to_string_lossy);
+    if json
+    {
+        format!(
+            "{{\"path\":{},\"type\":{},\"link\":{},\"size\":{},\"mode\":{},\"mtime\":{},\"uid\":\
+             {},\"gid\":{},\"owner\":{}}}",
+            json_string(&path),
+            json_string(entry.type_name()),
+            link_name.as_deref().map_or("null".to_string(), json_string),
+            entry.size,
+            entry.mode,
+            entry.mtime,
+            entry.uid,
+            entry.gid,
+            json_string(&owner),
+        )
+    }
+    else
+    {
+        let mtime = Epoch::from_unix_seconds(entry.mtime as f64);
+        let mut line = format!(
+            "{:<8} {:04o} {:<16} {:>12} {} {}",
+            entry.type_name(),
+            entry.mode & 0o7777,
+            owner,
+            entry.size,
+            mtime,
+            path
+        );
+        if let Some(link_name) = link_name
+        {
+            line.push_str(" -> ");
+            line.push_str(&link_name);
+        }
+        line
+    }
+}
+
+/// Prints the entries of a tarball to stdout for `--list`.
+fn list_tarball(
+    src: impl BufRead,
+    compression: Compression,
+    options: &CodecOptions,
+    json: bool,
+) -> Result<()>
+{
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    let res = decompress::list_from(src, compression, options, |entry| {
+        writeln!(stdout, "{}", format_entry(&entry, json))
+    })
+    .and_then(|_| stdout.flush());
+    match res
+    {
+        // e.g. piped to `head`.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(Error::from),
+    }
+}
+
+/// Writes the file matching `pattern` to stdout for `--cat`.
+fn cat_tarball(
+    src: impl BufRead,
+    compression: Compression,
+    options: &CodecOptions,
+    pattern: &str,
+) -> Result<()>
+{
+    let pattern = parse_patterns(Some(&[pattern.to_string()]))?.remove(0);
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    let res = decompress::cat_from(src, compression, options, &pattern, &mut stdout)
+        .and_then(|path| stdout.flush().map(|_| path));
+    match res
+    {
+        Ok(path) =>
+        {
+            info!("🥩 Wrote {} to stdout", path.display());
+            Ok(())
+        }
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(err) => Err(Error::from(err)),
+    }
+}
+
+/// Lists, prints a file of or extracts a tarball read from `src` depending on
+/// `raw_args`. Callers report the errors with `report_error()`.
+fn raw_from_reader(
+    raw_args: &RawArgs,
+    src: impl BufRead,
+    compression: Compression,
+    options: &CodecOptions,
+) -> Result<()>
+{
+    if raw_args.list
+    {
+        return list_tarball(src, compression, options, raw_args.json);
+    }
+    if let Some(pattern) = &raw_args.cat
+    {
+        return cat_tarball(src, compression, options, pattern);
+    }
+    let extract_options = extract_options(raw_args)?;
+    let outpath = outpath(raw_args)?;
+    let violations =
+        decompress::unpack_from(src, compression, &outpath, options, &extract_options)?;
+    report_violations(&violations);
+    info!("🥩 You have extracted your source at {}", outpath.display());
+    Ok(())
+}
+
+/// Lists, prints a file of or extracts a zip or 7z archive. Extracting goes
+/// through `decompress::unpack_archive()` so that `--max-ratio` is measured
+/// against the archive instead of the converted tarball.
+fn raw_from_archive(raw_args: &RawArgs, format: ArchiveFormat, src: &Path) -> Result<()>
+{
+    info!(%format, "Converting archive to a tarball on the fly");
+    if raw_args.list || raw_args.cat.is_some()
+    {
+        let ((), violations) = decompress::archive_as_tar(format, src, |tar| {
+            raw_from_reader(raw_args, tar, Compression::Not, &CodecOptions::default())
+                .map_err(io::Error::from)
+        })?;
+        report_violations(&violations);
+        return Ok(());
+    }
+    let extract_options = extract_options(raw_args)?;
+    let outpath = outpath(raw_args)?;
+    let violations = decompress::unpack_archive(format, src, &outpath, &extract_options)?;
+    report_violations(&violations);
+    info!("🥩 You have extracted your source at {}", outpath.display());
+    Ok(())
+}
+
+/// The output directory of the extraction. Defaults to the current directory.
+fn outpath(raw_args: &RawArgs) -> Result<PathBuf>
+{
+    match &raw_args.outdir
+    {
+        Some(outdir) => Ok(outdir.to_path_buf()),
+        None => Ok(std::env::current_dir().inspect_err(|e| {
+            error!(?e, "Unable to determine current directory!");
+        })?),
+    }
+}
+
+/// Extracts a tarball streamed from stdin i.e. `--target -`. The compression
+/// is detected from the magic bytes of the stream unless `--format` is set.
+fn raw_from_stdin(raw_args: &RawArgs) -> Result<()>
+{
+    let stdin = io::stdin();
+    let mut magic: Vec<u8> = Vec::new();
+    stdin.lock().take(MAGIC_PEEK_SIZE as u64).read_to_end(&mut magic)?;
+    let mime_type = match raw_args.format.map(|format| format.to_supported_format("-".into()))
+    {
+        Some(SupportedFormat::Compressed(compression, _)) => compression,
+        Some(_) =>
+        {
+            let err = Error::invalid_argument("Zip and 7z archives cannot be read from stdin.");
+            error!(?err);
+            return Err(err);
+        }
+        None => detect_compression(&magic).inspect_err(|err| {
+            eprintln!("{}", err);
+            error!(?err);
+        })?,
+    };
+    info!(?mime_type);
+    let dictionary = read_zstd_dict(raw_args.zstd_dict.as_deref())?;
+    let options = CodecOptions { dictionary: dictionary.as_deref(), ..Default::default() };
+    // Put back the bytes we have read for the detection.
+    let src = io::BufReader::new(io::Cursor::new(magic).chain(stdin.lock()));
+    raw_from_reader(raw_args, src, mime_type, &options).inspect_err(report_error)
+}
+
 /// Extracts a tarball as long as it is a supported mime-type. Arguments
 /// passed are based on `crate::cli::RawArgs`.
-pub fn raw_opts(raw_args: RawArgs, start_trace: bool) -> io::Result<()>
+pub fn raw_opts(raw_args: &RawArgs, start_trace: bool) -> Result<()>
 {
     if let Some(ref subcommand) = raw_args.subcommands
     {
@@ -43,6 +324,10 @@ pub fn raw_opts(raw_args: RawArgs, start_trace: bool) -> io::Result<()>
             {
                 print_completions(*shell, &mut cmd);
             }
+            crate::operations::cli::Subcommands::TrainZstdDict { outfile, max_size, samples } =>
+            {
+                write_zstd_dict(samples, *max_size, outfile)?;
+            }
         }
         Ok(())
     }
@@ -52,63 +337,59 @@ pub fn raw_opts(raw_args: RawArgs, start_trace: bool) -> io::Result<()>
         {
             if start_trace
             {
-                start_tracing();
+                start_tracing(raw_args.list || raw_args.cat.is_some());
             }
         }
         else if !raw_args.silent && start_trace
         {
-            start_tracing();
+            start_tracing(raw_args.list || raw_args.cat.is_some());
         }
         info!("🥩 Starting Raw.");
         debug!(?raw_args);
 
-        let target_path =
-            process_globs(&raw_args.target.ok_or("No target directory set").map_err(|err| {
+        if raw_args.target.as_deref() == Some(Path::new("-"))
+        {
+            return raw_from_stdin(raw_args);
+        }
+
+        // Fail early on invalid patterns or limits.
+        extract_options(raw_args)?;
+
+        let target_path = process_globs(
+            raw_args.target.as_deref().ok_or("No target directory set").map_err(|err| {
                 error!(err);
-                io::Error::new(io::ErrorKind::InvalidInput, err)
-            })?)?;
+                Error::invalid_argument(err)
+            })?,
+        )?;
         let target_path = target_path.canonicalize().unwrap_or(target_path);
         if target_path.is_file()
         {
-            match is_supported_format(&target_path)
+            let supported_format = match raw_args.format
+            {
+                Some(format) => Ok(format.to_supported_format(target_path.to_path_buf())),
+                None => is_supported_format(&target_path),
+            };
+            match supported_format
             {
                 Ok(target) => match target
                 {
-                    crate::common::SupportedFormat::Compressed(mime_type, src) =>
+                    SupportedFormat::Compressed(mime_type, src) =>
                     {
                         info!(?mime_type);
-                        let outpath = raw_args.outdir.unwrap_or(
-                            std::env::current_dir().inspect_err(|e| {
-                                error!(?e, "Unable to determine current directory!");
-                            })?,
-                        );
-                        match mime_type
-                        {
-                            crate::common::Compression::Gz =>
-                            {
-                                decompress::targz(&outpath, &src)?;
-                            }
-                            crate::common::Compression::Xz =>
-                            {
-                                decompress::tarxz(&outpath, &src)?;
-                            }
-                            crate::common::Compression::Zst | crate::common::Compression::Zstd =>
-                            {
-                                decompress::tarzst(&outpath, &src)?;
-                            }
-                            crate::common::Compression::Bz2 =>
-                            {
-                                decompress::tarbz2(&outpath, &src)?;
-                            }
-                            crate::common::Compression::Not =>
-                            {
-                                decompress::vanilla(&outpath, &src)?;
-                            }
-                        }
-                        info!("🥩 You have extracted your source at {}", outpath.display());
-                        Ok(())
+                        let dictionary = read_zstd_dict(raw_args.zstd_dict.as_deref())?;
+                        let options = CodecOptions {
+                            dictionary: dictionary.as_deref(),
+                            ..Default::default()
+                        };
+                        let src = io::BufReader::new(fs::File::open(&src)?);
+                        raw_from_reader(raw_args, src, mime_type, &options)
+                            .inspect_err(report_error)
+                    }
+                    SupportedFormat::Archive(format, src) =>
+                    {
+                        raw_from_archive(raw_args, format, &src).inspect_err(report_error)
                     }
-                    crate::common::SupportedFormat::Dir(_) =>
+                    SupportedFormat::Dir(_) =>
                     {
                         unreachable!(
                             "This should never be a directory since we already checked it!"
@@ -119,13 +400,13 @@ pub fn raw_opts(raw_args: RawArgs, start_trace: bool) -> io::Result<()>
                 {
                     eprintln!("{}", err);
                     error!(?err);
-                    Err(io::Error::new(io::ErrorKind::Unsupported, err.to_string()))
+                    Err(Error::Format(err))
                 }
             }
         }
         else
         {
-            let err = io::Error::new(io::ErrorKind::Unsupported, "Directory detected.");
+            let err = Error::invalid_argument("Directory detected.");
             error!(?err);
             Err(err)
         }
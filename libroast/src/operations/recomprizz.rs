@@ -7,12 +7,18 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::{
+    common::Compression,
+    error::{
+        Error,
+        Result,
+    },
     operations::{
         cli::{
             RawArgs,
             RecomprizzArgs,
             RoastArgs,
             print_completions,
+            write_zstd_dict,
         },
         raw::raw_opts,
         roast::roast_opts,
@@ -25,10 +31,7 @@ use crate::{
 };
 use clap::CommandFactory;
 use regex::Regex;
-use std::{
-    io,
-    path::PathBuf,
-};
+use std::path::PathBuf;
 #[allow(unused_imports)]
 use tracing::{
     Level,
@@ -45,7 +48,7 @@ use tracing::{
 ///
 /// This function relies on the arguments provided by
 /// `crate::cli::RecomprizzArgs`.
-pub fn recomprizz_opts(recomprizz_args: RecomprizzArgs) -> io::Result<()>
+pub fn recomprizz_opts(recomprizz_args: RecomprizzArgs) -> Result<()>
 {
     if let Some(ref subcommand) = recomprizz_args.subcommands
     {
@@ -56,6 +59,10 @@ pub fn recomprizz_opts(recomprizz_args: RecomprizzArgs) -> io::Result<()>
             {
                 print_completions(*shell, &mut cmd);
             }
+            crate::operations::cli::Subcommands::TrainZstdDict { outfile, max_size, samples } =>
+            {
+                write_zstd_dict(samples, *max_size, outfile)?;
+            }
         }
     }
     else
@@ -63,14 +70,16 @@ pub fn recomprizz_opts(recomprizz_args: RecomprizzArgs) -> io::Result<()>
         #[allow(clippy::if_same_then_else)] // It's not actually the same
         if cfg!(feature = "obs")
         {
-            start_tracing();
+            start_tracing(false);
         }
         else if !recomprizz_args.silent
         {
-            start_tracing();
+            start_tracing(false);
         }
         info!("📤 Starting Recomprizz.");
         debug!(?recomprizz_args);
+        // Fail early before extracting anything.
+        recomprizz_args.compression.validate_level(recomprizz_args.level)?;
         let tmp_binding_for_raw = tempfile::Builder::new()
             .prefix(".raaaaaaaaaaaaaaaaawwwwww")
             .rand_bytes(8)
@@ -83,18 +92,31 @@ pub fn recomprizz_opts(recomprizz_args: RecomprizzArgs) -> io::Result<()>
         let target = process_globs(&recomprizz_args.target.ok_or("No target provided.").map_err(
             |err| {
                 error!(err);
-                io::Error::new(io::ErrorKind::InvalidInput, err)
+                Error::invalid_argument(err)
             },
         )?)?;
         let target = target.canonicalize().unwrap_or(target);
         let raw_args = RawArgs {
             target: Some(target.clone()),
+            format: recomprizz_args.format,
             outdir: Some(outpath_for_raw.to_path_buf()),
+            list: false,
+            json: false,
+            cat: None,
+            include: None,
+            exclude: None,
+            strip_components: 0,
+            zstd_dict: recomprizz_args.input_zstd_dict.clone(),
+            safe_extract: recomprizz_args.safe_extract,
+            max_total_size: recomprizz_args.max_total_size,
+            max_entries: recomprizz_args.max_entries,
+            max_file_size: recomprizz_args.max_file_size,
+            max_ratio: recomprizz_args.max_ratio,
             silent: recomprizz_args.silent,
             subcommands: None,
         };
 
-        raw_opts(raw_args, false)?;
+        raw_opts(&raw_args, false)?;
 
         let file_extension = recomprizz_args.compression.to_extension();
 
@@ -105,9 +127,8 @@ pub fn recomprizz_opts(recomprizz_args: RecomprizzArgs) -> io::Result<()>
                 Some(pattern) =>
                 {
                     info!("Renaming with regex `{}` with pattern `{}`.", rename_string, pattern);
-                    let re = Regex::new(&rename_string).map_err(|err| {
+                    let re = Regex::new(&rename_string).inspect_err(|err| {
                         error!(?err);
-                        io::Error::other(err)
                     })?;
                     let filename = target.file_name().unwrap_or_default().to_string_lossy();
                     let after = re.replace_all(&filename, pattern);
@@ -121,37 +142,45 @@ pub fn recomprizz_opts(recomprizz_args: RecomprizzArgs) -> io::Result<()>
             },
             None =>
             {
-                let supported_format = is_supported_format(&target).map_err(|err| {
-                    error!(?err);
-                    io::Error::other(err)
-                })?;
-                match supported_format
+                let supported_format = match recomprizz_args.format
+                {
+                    Some(format) => format.to_supported_format(target.to_path_buf()),
+                    None => is_supported_format(&target).inspect_err(|err| {
+                        error!(?err);
+                    })?,
+                };
+                let filename = target.file_name().unwrap_or_default().to_string_lossy();
+                let name = match supported_format
                 {
-                    crate::common::SupportedFormat::Compressed(compression, path_buf) =>
+                    crate::common::SupportedFormat::Compressed(..) =>
                     {
-                        let filename = path_buf.file_name().unwrap_or_default().to_string_lossy();
-                        match filename.rsplit_once(&compression.to_extension())
-                        {
-                            Some((name, _)) => name.to_string(),
-                            None =>
-                            {
-                                warn!("Not able to remove extension.");
-                                warn!(
-                                    "The file might be a supported format but is using a \
-                                     different file extension."
-                                );
-                                warn!(
-                                    "Not removing old file extension. This will result to an \
-                                     undesirable rename of the file."
-                                );
-                                filename.to_string()
-                            }
-                        }
+                        Compression::split_extension(&filename).map(|(name, _)| name)
+                    }
+                    crate::common::SupportedFormat::Archive(format, _) =>
+                    {
+                        filename.strip_suffix(&format.to_extension())
                     }
                     crate::common::SupportedFormat::Dir(_) =>
                     {
                         unreachable!("Only files are supported in `recomprizz`.")
                     }
+                };
+                match name
+                {
+                    Some(name) => name.to_string(),
+                    None =>
+                    {
+                        warn!("Not able to remove extension.");
+                        warn!(
+                            "The file might be a supported format but is using a different file \
+                             extension."
+                        );
+                        warn!(
+                            "Not removing old file extension. This will result to an undesirable \
+                             rename of the file."
+                        );
+                        filename.to_string()
+                    }
                 }
             }
         };
@@ -164,11 +193,23 @@ pub fn recomprizz_opts(recomprizz_args: RecomprizzArgs) -> io::Result<()>
             exclude: recomprizz_args.exclude,
             outfile: Some(PathBuf::from(&out_filename)),
             outdir: recomprizz_args.outdir,
+            compression: None,
             preserve_root: false,
             reproducible: recomprizz_args.reproducible,
             ignore_git: recomprizz_args.ignore_git,
             ignore_hidden: recomprizz_args.ignore_hidden,
+            dereference: recomprizz_args.dereference,
+            respect_ignore_files: recomprizz_args.respect_ignore_files,
+            max_file_size: None,
+            exclude_type: None,
+            exclude_older_than: None,
+            exclude_newer_than: None,
             include: recomprizz_args.include,
+            level: recomprizz_args.level,
+            threads: recomprizz_args.threads,
+            zstd_dict: recomprizz_args.zstd_dict,
+            dry_run: false,
+            json: false,
             silent: recomprizz_args.silent,
             subcommands: None,
         };
@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: MPL-2.0
+
+// Copyright (C) 2025 Soc Virnyl Estela and contributors
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Filters of `--max-file-size`, `--exclude-type`, `--exclude-older-than` and
+//! `--exclude-newer-than`. Skipped entries are counted so that a summary can be
+//! logged once the archive is planned.
+
+use crate::{
+    manifest::{
+        EntryKind,
+        Manifest,
+    },
+    operations::cli::{
+        EntryType,
+        RoastArgs,
+    },
+};
+use clap::ValueEnum;
+use hifitime::Epoch;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs,
+    path::Path,
+    sync::{
+        Mutex,
+        PoisonError,
+    },
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+#[allow(unused_imports)]
+use tracing::{
+    Level,
+    debug,
+    error,
+    info,
+    trace,
+    warn,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// Why an entry was skipped by `EntryFilters`.
+pub(crate) enum SkipReason
+{
+    TooLarge,
+    Type(EntryType),
+    OlderThan,
+    NewerThan,
+}
+
+impl fmt::Display for SkipReason
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            SkipReason::TooLarge => write!(f, "larger than `--max-file-size`"),
+            SkipReason::Type(entry_type) =>
+            {
+                let name = entry_type.to_possible_value().map(|value| value.get_name().to_string());
+                write!(f, "of type `{}` in `--exclude-type`", name.unwrap_or_default())
+            }
+            SkipReason::OlderThan => write!(f, "older than `--exclude-older-than`"),
+            SkipReason::NewerThan => write!(f, "newer than `--exclude-newer-than`"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// Skips the entries of the target directory by their size, type or age. This
+/// is shared by the threads of `super::helpers::filter_paths()`.
+pub(crate) struct EntryFilters
+{
+    max_file_size: Option<u64>,
+    types: Vec<EntryType>,
+    older_than: Option<Epoch>,
+    newer_than: Option<Epoch>,
+    /// Number of entries and their total size in bytes for each reason.
+    skipped: Mutex<BTreeMap<SkipReason, (u64, u64)>>,
+}
+
+impl EntryFilters
+{
+    pub(crate) fn new(roast_args: &RoastArgs) -> Self
+    {
+        EntryFilters {
+            max_file_size: roast_args.max_file_size,
+            types: roast_args.exclude_type.clone().unwrap_or_default(),
+            older_than: roast_args.exclude_older_than,
+            newer_than: roast_args.exclude_newer_than,
+            skipped: Mutex::default(),
+        }
+    }
+
+    fn is_empty(&self) -> bool
+    {
+        self.max_file_size.is_none()
+            && self.types.is_empty()
+            && self.older_than.is_none()
+            && self.newer_than.is_none()
+    }
+
+    /// Whether `path` is skipped. The skipped entry is logged and counted.
+    /// Directories are never skipped here. See `skip_empty_dirs()`.
+    pub(crate) fn skip(&self, path: &Path, dereference: bool) -> bool
+    {
+        let Some((reason, size)) = self.skip_reason(path, dereference)
+        else
+        {
+            return false;
+        };
+        debug!(?path, size, "Skipping entry {}", reason);
+        self.record(reason, size);
+        true
+    }
+
+    fn skip_reason(&self, path: &Path, dereference: bool) -> Option<(SkipReason, u64)>
+    {
+        if self.is_empty()
+        {
+            return None;
+        }
+        let metadata =
+            if dereference { fs::metadata(path) } else { fs::symlink_metadata(path) }.ok()?;
+        let entry_type = if metadata.is_symlink()
+        {
+            EntryType::Symlink
+        }
+        else if metadata.is_file()
+        {
+            EntryType::Regular
+        }
+        else
+        {
+            return None;
+        };
+        let size = metadata.len();
+        if self.types.contains(&entry_type)
+        {
+            return Some((SkipReason::Type(entry_type), size));
+        }
+        if entry_type == EntryType::Regular && self.max_file_size.is_some_and(|max| size > max)
+        {
+            return Some((SkipReason::TooLarge, size));
+        }
+        let modified = to_epoch(metadata.modified().ok()?);
+        if self.older_than.is_some_and(|date| modified < date)
+        {
+            return Some((SkipReason::OlderThan, size));
+        }
+        if self.newer_than.is_some_and(|date| modified > date)
+        {
+            return Some((SkipReason::NewerThan, size));
+        }
+        None
+    }
+
+    /// Drops the directory entries of `manifest` with `--exclude-type
+    /// empty-dir`. Only empty directories are left after
+    /// `Manifest::retain_empty_dirs()` so this must be called after it.
+    pub(crate) fn skip_empty_dirs(&self, manifest: &mut Manifest)
+    {
+        if !self.types.contains(&EntryType::EmptyDir)
+        {
+            return;
+        }
+        let reason = SkipReason::Type(EntryType::EmptyDir);
+        manifest.retain(|entry| {
+            if entry.kind != EntryKind::Dir
+            {
+                return true;
+            }
+            debug!(path = ?entry.source, "Skipping entry {}", reason);
+            self.record(reason, 0);
+            false
+        });
+    }
+
+    fn record(&self, reason: SkipReason, size: u64)
+    {
+        let mut skipped = self.skipped.lock().unwrap_or_else(PoisonError::into_inner);
+        let (count, total) = skipped.entry(reason).or_default();
+        *count += 1;
+        *total += size;
+    }
+
+    /// Logs how many entries were skipped for each reason.
+    pub(crate) fn log_summary(&self)
+    {
+        let skipped = self.skipped.lock().unwrap_or_else(PoisonError::into_inner);
+        for (reason, (count, total)) in skipped.iter()
+        {
+            info!("🧹 Skipped {} entries {} with {} bytes in total.", count, reason, total);
+        }
+    }
+}
+
+/// Converts a modification time to an `Epoch`. Times before 1970 are negative.
+fn to_epoch(time: SystemTime) -> Epoch
+{
+    match time.duration_since(UNIX_EPOCH)
+    {
+        Ok(since) => Epoch::from_unix_seconds(since.as_secs_f64()),
+        Err(err) => Epoch::from_unix_seconds(-err.duration().as_secs_f64()),
+    }
+}
@@ -1,11 +1,25 @@
+use super::{
+    filters::EntryFilters,
+    patterns::{
+        IgnoreFiles,
+        PathMatcher,
+    },
+};
+use crate::{
+    decompress::escapes_outdir,
+    manifest::{
+        EntryKind,
+        Manifest,
+        ManifestEntry,
+    },
+    operations::cli::RoastArgs,
+    utils::canonicalize_entry,
+};
 use rayon::prelude::*;
 use std::{
     fs,
     io,
-    path::{
-        Path,
-        PathBuf,
-    },
+    path::Path,
 };
 #[allow(unused_imports)]
 use tracing::{
@@ -46,57 +60,87 @@ pub(crate) fn is_hidden(entry: &Path, hidden: bool, ignore_git: bool, root: &Pat
     }
 }
 
-pub(crate) fn is_excluded(
-    entry_as_path_canonicalized: &Path,
-    exclude_canonicalized_paths: &[PathBuf],
-) -> bool
+#[derive(Debug, Clone, Copy)]
+/// Options of the directory walk of `filter_paths()`.
+pub(crate) struct WalkOptions<'a>
 {
-    exclude_canonicalized_paths.par_iter().any(|exclude| {
-        let is_stripped = entry_as_path_canonicalized.strip_prefix(exclude);
-        debug!(?is_stripped, ?entry_as_path_canonicalized, ?exclude);
-        is_stripped.is_ok()
-    })
+    /// Skip dotfiles. See `is_hidden()`.
+    pub(crate) hidden: bool,
+    /// Skip `.git*` paths. See `is_hidden()`.
+    pub(crate) ignore_git: bool,
+    pub(crate) excludes: &'a PathMatcher,
+    /// Follow symlinks instead of archiving them as symlinks.
+    pub(crate) dereference: bool,
+    /// Skip the paths ignored by the `.gitignore`, `.ignore` and `.roastignore`
+    /// files in the walked directories.
+    pub(crate) ignore_files: bool,
+    /// Skip entries by their size, type or age.
+    pub(crate) filters: &'a EntryFilters,
 }
 
+impl<'a> WalkOptions<'a>
+{
+    pub(crate) fn new(
+        roast_args: &RoastArgs,
+        excludes: &'a PathMatcher,
+        filters: &'a EntryFilters,
+    ) -> Self
+    {
+        WalkOptions {
+            hidden: roast_args.ignore_hidden,
+            ignore_git: roast_args.ignore_git,
+            excludes,
+            dereference: roast_args.dereference,
+            ignore_files: roast_args.respect_ignore_files,
+            filters,
+        }
+    }
+}
+
+/// Plans `entry_as_path_canonicalized` and everything below it. `root` is the
+/// path in the archive that corresponds to `target_path`. `ignore_files` are
+/// the ignore files of `target_path` and its parents.
 pub(crate) fn helper_archiver(
     entry_as_path_canonicalized: &Path,
     target_path: &Path,
-    hidden: bool,
-    ignore_git: bool,
     root: &Path,
-    exclude_paths: &[PathBuf],
-) -> io::Result<()>
+    options: &WalkOptions,
+    ignore_files: &IgnoreFiles,
+) -> io::Result<Vec<ManifestEntry>>
 {
-    if entry_as_path_canonicalized.is_dir()
+    let mut entries = Vec::new();
+    let entry_stripped_by_target_path = entry_as_path_canonicalized
+        .strip_prefix(target_path)
+        .unwrap_or(entry_as_path_canonicalized);
+    let genesis_path = root.join(entry_stripped_by_target_path);
+    if is_hidden(entry_as_path_canonicalized, options.hidden, options.ignore_git, target_path)
     {
-        if !is_hidden(entry_as_path_canonicalized, hidden, ignore_git, root)
-        {
-            let entry_stripped_by_target_path = entry_as_path_canonicalized
-                .strip_prefix(target_path)
-                .unwrap_or(entry_as_path_canonicalized);
-            let genesis_dir = &root.join(entry_stripped_by_target_path);
-            fs::create_dir_all(genesis_dir)?;
-            filter_paths(
-                entry_as_path_canonicalized,
-                genesis_dir,
-                hidden,
-                ignore_git,
-                exclude_paths,
-            )?;
-        }
+        return Ok(entries);
+    }
+    if !options.dereference && entry_as_path_canonicalized.is_symlink()
+    {
+        entries.push(ManifestEntry::new(
+            entry_as_path_canonicalized,
+            genesis_path,
+            EntryKind::Symlink,
+        ));
+    }
+    else if entry_as_path_canonicalized.is_dir()
+    {
+        entries.extend(filter_paths(
+            entry_as_path_canonicalized,
+            &genesis_path,
+            options,
+            ignore_files,
+        )?);
+        entries.push(ManifestEntry::new(entry_as_path_canonicalized, genesis_path, EntryKind::Dir));
     }
     else if entry_as_path_canonicalized.is_file()
-        && !is_hidden(entry_as_path_canonicalized, hidden, ignore_git, root)
     {
-        let entry_stripped_by_target_path = entry_as_path_canonicalized
-            .strip_prefix(target_path)
-            .unwrap_or(entry_as_path_canonicalized);
         let entry_as_path_canonicalized_parent =
             entry_as_path_canonicalized.parent().unwrap_or(target_path);
-        let genesis_path = &root.join(entry_stripped_by_target_path);
         let genesis_path_parent = genesis_path.parent().unwrap_or(root);
-        if is_excluded(entry_as_path_canonicalized, exclude_paths)
-            && (*genesis_path_parent != *root)
+        if options.excludes.is_match(entry_as_path_canonicalized) && (*genesis_path_parent != *root)
         {
             warn!(
                 "⚠️ Adding file `{}` that is WITHIN an EXCLUDED directory `{}`.",
@@ -104,56 +148,85 @@ pub(crate) fn helper_archiver(
                 &entry_as_path_canonicalized_parent.display()
             );
         }
-        fs::create_dir_all(genesis_path_parent)?;
-        fs::copy(entry_as_path_canonicalized, genesis_path)?;
+        entries.push(ManifestEntry::new(
+            entry_as_path_canonicalized,
+            genesis_path,
+            EntryKind::File,
+        ));
     }
-    Ok(())
+    Ok(entries)
 }
 
+/// Plans the contents of `target_path` under `root` in the archive. Nothing is
+/// copied. The files are read from `target_path` once the archive is built.
+/// `ignore_files` are the ignore files of the parents of `target_path`.
 pub(crate) fn filter_paths(
     target_path: &Path,
     root: &Path,
-    hidden: bool,
-    ignore_git: bool,
-    exclude_paths: &[PathBuf],
-) -> io::Result<()>
+    options: &WalkOptions,
+    ignore_files: &IgnoreFiles,
+) -> io::Result<Vec<ManifestEntry>>
 {
+    // `target_path` is a symlink to a directory if dereferenced. Entries are
+    // stripped by their canonicalized parent so this has to match.
+    let target_path = &target_path.canonicalize().unwrap_or(target_path.to_path_buf());
+    let ignore_files = &if options.ignore_files
+    {
+        ignore_files.with_dir(target_path)
+    }
+    else
+    {
+        IgnoreFiles::default()
+    };
     let target_dir = fs::read_dir(target_path)
         .inspect_err(|err| {
             error!(?err);
         })?
         .flatten();
 
-    target_dir.par_bridge().into_par_iter().try_for_each(|entry| {
-        let entry_as_path = &entry.path();
-        let entry_as_path_canonicalized =
-            &entry_as_path.canonicalize().unwrap_or(entry_as_path.to_path_buf());
-        if exclude_paths.is_empty()
-        {
-            helper_archiver(
-                entry_as_path_canonicalized,
-                target_path,
-                hidden,
-                ignore_git,
-                root,
-                exclude_paths,
-            )
-        }
-        else
-        {
-            if !is_excluded(entry_as_path_canonicalized, exclude_paths)
+    let planned: Vec<Vec<ManifestEntry>> = target_dir
+        .par_bridge()
+        .into_par_iter()
+        .map(|entry| {
+            let entry_as_path = &entry.path();
+            // Symlinks are only followed when they are read so that a dereferenced
+            // file keeps the path of its symlink.
+            let entry_as_path_canonicalized = &canonicalize_entry(entry_as_path, false);
+            if options.excludes.is_match(entry_as_path_canonicalized)
+                || ignore_files.is_ignored(entry_as_path_canonicalized)
+                || options.filters.skip(entry_as_path_canonicalized, options.dereference)
             {
-                helper_archiver(
-                    entry_as_path_canonicalized,
-                    target_path,
-                    hidden,
-                    ignore_git,
-                    root,
-                    exclude_paths,
-                )?;
+                return Ok(Vec::new());
             }
-            Ok(())
+            helper_archiver(entry_as_path_canonicalized, target_path, root, options, ignore_files)
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(planned.into_iter().flatten().collect())
+}
+
+/// Warns about the symlinks in `manifest` that point outside of `root`, the
+/// path of the target directory in the archive. These are kept as they are
+/// but are likely dangling once extracted.
+pub(crate) fn warn_escaping_symlinks(manifest: &Manifest, root: &Path)
+{
+    manifest.entries().filter(|entry| entry.kind == EntryKind::Symlink).for_each(|entry| {
+        let Ok(link_target) = fs::read_link(&entry.source)
+        else
+        {
+            return;
+        };
+        let base = entry
+            .archive_path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(root).ok())
+            .unwrap_or(Path::new(""));
+        if escapes_outdir(base, &link_target)
+        {
+            warn!(
+                "⚠️ Symlink `{}` points OUTSIDE of the target directory: {}",
+                entry.source.display(),
+                link_target.display()
+            );
         }
-    })?;
-    Ok(())
+    });
 }
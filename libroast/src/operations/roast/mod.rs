@@ -5,30 +5,60 @@
 // This Source Code Form is subject to the terms of the Mozilla Public
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
+pub mod filters;
 pub mod helpers;
+pub mod patterns;
 use crate::{
+    codec::CodecOptions,
+    common::{
+        Compression,
+        UnsupportedFormat,
+    },
     compress,
+    error::{
+        Error,
+        Result,
+    },
+    manifest::{
+        EntryKind,
+        EntryRule,
+        Manifest,
+        ManifestEntry,
+    },
     operations::cli::{
         self,
         RoastArgs,
         print_completions,
+        write_zstd_dict,
     },
     utils::{
+        canonicalize_entry,
+        json_string,
         process_globs,
         start_tracing,
     },
 };
 use clap::CommandFactory;
+use filters::EntryFilters;
 use helpers::{
+    WalkOptions,
     filter_paths,
-    is_excluded,
+    warn_escaping_symlinks,
+};
+use patterns::{
+    IgnoreFiles,
+    PathMatcher,
+    is_literal,
 };
 use rayon::prelude::*;
 use std::{
     fs::{
         self,
     },
-    io,
+    io::{
+        self,
+        Write,
+    },
     path::{
         Path,
         PathBuf,
@@ -44,44 +74,6 @@ use tracing::{
     warn,
 };
 
-fn get_all_files(updated_paths: &mut Vec<PathBuf>, workdir: &Path) -> io::Result<()>
-{
-    if workdir.is_dir()
-    {
-        let processed_paths: Vec<PathBuf> = workdir
-            .read_dir()?
-            .par_bridge()
-            .flatten()
-            .into_par_iter()
-            .map(|f| {
-                debug!(?f);
-                f.path()
-            })
-            .filter(|p| {
-                p.canonicalize().unwrap_or(p.to_path_buf())
-                    != workdir.canonicalize().unwrap_or(workdir.to_path_buf())
-            })
-            .collect();
-
-        if processed_paths.is_empty()
-        {
-            updated_paths.push(workdir.canonicalize().unwrap_or(workdir.to_path_buf()))
-        }
-        processed_paths.into_iter().try_for_each(|f| -> io::Result<()> {
-            if f.is_dir()
-            {
-                get_all_files(updated_paths, &f.canonicalize().unwrap_or(f.to_path_buf()))?;
-            }
-            else if f.is_file()
-            {
-                updated_paths.push(f.canonicalize().unwrap_or(f.to_path_buf()));
-            }
-            Ok(())
-        })?;
-    }
-    Ok(())
-}
-
 /// This function helps process a list of additional paths separated by commas.
 pub(crate) fn get_additional_paths(adtnl_path: &str, root: &Path) -> (PathBuf, PathBuf)
 {
@@ -98,160 +90,376 @@ pub(crate) fn get_additional_paths(adtnl_path: &str, root: &Path) -> (PathBuf, P
 }
 
 /// This function helps process additional paths
-/// during the archiving process.
+/// during the planning of the archive.
 pub(crate) fn process_additional_paths(
     additional_paths: &[String],
     target_path: &Path,
-    exclude_canonicalized_paths: &[PathBuf],
-    setup_workdir: &Path,
+    excludes: &PathMatcher,
+    archive_root: &Path,
     roast_args: &cli::RoastArgs,
-) -> io::Result<()>
+) -> io::Result<Vec<ManifestEntry>>
 {
-    additional_paths.par_iter().try_for_each(|adtnlp| {
-        debug!(?adtnlp);
-        let (additional_from_path, additional_to_path) =
-            get_additional_paths(adtnlp, setup_workdir);
-        debug!(?additional_from_path, ?additional_to_path);
-        let src_canonicalized =
-            additional_from_path.canonicalize().unwrap_or(additional_from_path.to_path_buf());
-        debug!(?src_canonicalized);
+    let planned: Vec<Vec<ManifestEntry>> = additional_paths
+        .par_iter()
+        .map(|adtnlp| -> io::Result<Vec<ManifestEntry>> {
+            debug!(?adtnlp);
+            let rule = EntryRule::AdditionalPath(adtnlp.clone());
+            let (additional_from_path, additional_to_path) =
+                get_additional_paths(adtnlp, archive_root);
+            debug!(?additional_from_path, ?additional_to_path);
+            let src_canonicalized =
+                canonicalize_entry(&additional_from_path, roast_args.dereference);
+            debug!(?src_canonicalized);
 
-        if src_canonicalized.is_file()
-        {
-            let tgt_stripped =
-                additional_to_path.strip_prefix(setup_workdir).unwrap_or(Path::new("/"));
-            let target_with_tgt = &target_path.join(tgt_stripped);
-            if is_excluded(target_with_tgt, exclude_canonicalized_paths)
+            if src_canonicalized.is_file() || src_canonicalized.is_symlink()
             {
-                warn!(
-                    "⚠️ Directory `{}` is WITHIN an EXCLUDED path. Added a file OUTSIDE of target \
-                     directory: {}",
-                    &target_with_tgt.display(),
-                    &src_canonicalized.display()
-                );
+                let tgt_stripped =
+                    additional_to_path.strip_prefix(archive_root).unwrap_or(Path::new("/"));
+                let target_with_tgt = &target_path.join(tgt_stripped);
+                if excludes.is_match(target_with_tgt)
+                {
+                    warn!(
+                        "⚠️ Directory `{}` is WITHIN an EXCLUDED path. Added a file OUTSIDE of \
+                         target directory: {}",
+                        &target_with_tgt.display(),
+                        &src_canonicalized.display()
+                    );
+                }
+                let kind = if src_canonicalized.is_symlink()
+                {
+                    EntryKind::Symlink
+                }
+                else
+                {
+                    EntryKind::File
+                };
+                let additional_to_path =
+                    additional_to_path.join(additional_from_path.file_name().unwrap_or_default());
+                Ok(vec![
+                    ManifestEntry::new(src_canonicalized, additional_to_path, kind).with_rule(rule),
+                ])
             }
-            // create directory and warn if it's an excluded directory
-            fs::create_dir_all(&additional_to_path)?;
-            // Copy file to target path
-            fs::copy(
-                &src_canonicalized,
-                additional_to_path.join(additional_from_path.file_name().unwrap_or_default()),
-            )?;
-            Ok(())
-        }
-        else if src_canonicalized.is_dir()
-        {
-            let tgt_stripped =
-                additional_to_path.strip_prefix(setup_workdir).unwrap_or(Path::new("/"));
-            let target_with_tgt = &target_path.join(tgt_stripped);
-            if is_excluded(target_with_tgt, exclude_canonicalized_paths)
+            else if src_canonicalized.is_dir()
             {
-                warn!(
-                    "⚠️ ADDITIONAL directory that was WITHIN one of the EXCLUDED paths was added \
-                     back from OUTSIDE target path: {}",
-                    &target_with_tgt.display()
-                );
-                warn!("⚠️ This may not contain the same contents!");
+                let tgt_stripped =
+                    additional_to_path.strip_prefix(archive_root).unwrap_or(Path::new("/"));
+                let target_with_tgt = &target_path.join(tgt_stripped);
+                if excludes.is_match(target_with_tgt)
+                {
+                    warn!(
+                        "⚠️ ADDITIONAL directory that was WITHIN one of the EXCLUDED paths was \
+                         added back from OUTSIDE target path: {}",
+                        &target_with_tgt.display()
+                    );
+                    warn!("⚠️ This may not contain the same contents!");
+                }
+                let new_additional_to_path =
+                    additional_to_path.join(src_canonicalized.file_name().unwrap_or_default());
+                let mut entries = filter_paths(
+                    &src_canonicalized,
+                    &new_additional_to_path,
+                    &WalkOptions::new(roast_args, &PathMatcher::empty(), &EntryFilters::default()),
+                    &IgnoreFiles::default(),
+                )?;
+                entries.push(ManifestEntry::new(
+                    &src_canonicalized,
+                    new_additional_to_path,
+                    EntryKind::Dir,
+                ));
+                Ok(entries.into_iter().map(|entry| entry.with_rule(rule.clone())).collect())
             }
-            let new_additional_to_path =
-                additional_to_path.join(src_canonicalized.file_name().unwrap_or_default());
-            fs::create_dir_all(&new_additional_to_path)?;
-            filter_paths(
-                &src_canonicalized,
-                &new_additional_to_path,
-                roast_args.ignore_hidden,
-                roast_args.ignore_git,
-                &[],
-            )
-        }
-        else
-        {
-            Ok(())
-        }
-    })?;
-    Ok(())
+            else
+            {
+                Ok(Vec::new())
+            }
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(planned.into_iter().flatten().collect())
 }
 
 /// This processes included paths and filters out excluded paths.
 /// Any included paths that are excluded are always excluded only if it matches
 /// equally. Any included path that has an excluded parent path are included.
+/// Each path is paired with the `--include` value that selected it.
 pub(crate) fn process_include_paths(
-    include_paths: &[PathBuf],
-    exclude_canonicalized_paths: &[PathBuf],
+    include_paths: &[(PathBuf, String)],
+    excludes: &PathMatcher,
     target_path: &Path,
-    setup_workdir: &Path,
+    archive_root: &Path,
     roast_args: &cli::RoastArgs,
-) -> io::Result<()>
+) -> io::Result<Vec<ManifestEntry>>
 {
-    include_paths.par_iter().try_for_each(|include_path| {
-        let include_from_path = &target_path.join(include_path);
-        let include_from_path =
-            include_from_path.canonicalize().unwrap_or(include_from_path.to_path_buf());
-        if !include_from_path.exists()
-        {
-            let err = io::Error::new(
-                io::ErrorKind::NotFound,
-                "Path does not exist. This means that this path is not WITHIN the target \
-                 directory.",
-            );
-            error!(?err);
-            return Err(err);
-        }
-
-        let include_to_path = &setup_workdir.join(include_path);
-        debug!(?include_path, ?include_from_path, ?include_to_path);
-        if include_from_path.is_dir()
-        {
-            if is_excluded(&include_from_path, exclude_canonicalized_paths)
+    let planned: Vec<Vec<ManifestEntry>> = include_paths
+        .par_iter()
+        .map(|(include_path, value)| -> io::Result<Vec<ManifestEntry>> {
+            let mut entries = Vec::new();
+            let include_from_path = &target_path.join(include_path);
+            let include_from_path = canonicalize_entry(include_from_path, roast_args.dereference);
+            if !include_from_path.exists() && !include_from_path.is_symlink()
             {
-                warn!(
-                    "⚠️ INCLUDED directory that is EXCLUDED will be IGNORED: {}",
-                    &include_from_path.display()
+                let err = Error::filter(
+                    "Path does not exist. This means that this path is not WITHIN the target \
+                     directory.",
                 );
+                error!(?err);
+                return Err(io::Error::from(err));
             }
-            else
+
+            let include_to_path = archive_root.join(include_path);
+            debug!(?include_path, ?include_from_path, ?include_to_path);
+            if include_from_path.is_symlink()
             {
-                filter_paths(
-                    &include_from_path,
+                if excludes.is_match(&include_from_path)
+                {
+                    warn!(
+                        "⚠️ EXCLUDED symlink `{}` has also been declared INCLUDED. Adding symlink \
+                         takes precedence.",
+                        &include_from_path.display()
+                    );
+                }
+                entries.push(ManifestEntry::new(
+                    include_from_path,
                     include_to_path,
-                    roast_args.ignore_hidden,
-                    roast_args.ignore_git,
-                    &[],
-                )?;
+                    EntryKind::Symlink,
+                ));
             }
-        }
-        else if include_from_path.is_file()
-        {
-            let include_from_path_parent =
-                include_from_path.parent().unwrap_or(target_path).to_path_buf();
-            let include_to_path_parent =
-                include_to_path.parent().unwrap_or(setup_workdir).to_path_buf();
-            if is_excluded(&include_from_path_parent, exclude_canonicalized_paths)
+            else if include_from_path.is_dir()
             {
-                warn!(
-                    "⚠️ Path `{}` WITHIN an EXCLUDED path has added a file IN target directory. \
-                     Added file: {}",
-                    &include_from_path_parent.display(),
-                    &include_from_path.display()
-                );
+                if excludes.is_match(&include_from_path)
+                {
+                    warn!(
+                        "⚠️ INCLUDED directory that is EXCLUDED will be IGNORED: {}",
+                        &include_from_path.display()
+                    );
+                }
+                else
+                {
+                    entries.extend(filter_paths(
+                        &include_from_path,
+                        &include_to_path,
+                        &WalkOptions::new(
+                            roast_args,
+                            &PathMatcher::empty(),
+                            &EntryFilters::default(),
+                        ),
+                        &IgnoreFiles::default(),
+                    )?);
+                }
             }
-            if is_excluded(&include_from_path, exclude_canonicalized_paths)
+            else if include_from_path.is_file()
             {
-                warn!(
-                    "⚠️ EXCLUDED file `{}` has also been declared INCLUDED. Adding file takes \
-                     precedence. Added file: {}",
-                    &include_from_path.display(),
-                    &include_from_path.display()
-                );
+                let include_from_path_parent =
+                    include_from_path.parent().unwrap_or(target_path).to_path_buf();
+                if excludes.is_match(&include_from_path_parent)
+                {
+                    warn!(
+                        "⚠️ Path `{}` WITHIN an EXCLUDED path has added a file IN target \
+                         directory. Added file: {}",
+                        &include_from_path_parent.display(),
+                        &include_from_path.display()
+                    );
+                }
+                if excludes.is_match(&include_from_path)
+                {
+                    warn!(
+                        "⚠️ EXCLUDED file `{}` has also been declared INCLUDED. Adding file takes \
+                         precedence. Added file: {}",
+                        &include_from_path.display(),
+                        &include_from_path.display()
+                    );
+                }
+                entries.push(ManifestEntry::new(
+                    include_from_path,
+                    include_to_path,
+                    EntryKind::File,
+                ));
             }
-            // create directory and warn if it's an excluded directory
-            fs::create_dir_all(&include_to_path_parent)?;
-            // Copy file to target path
-            fs::copy(include_from_path, include_to_path)?;
+            let rule = EntryRule::Include(value.clone());
+            Ok(entries.into_iter().map(|entry| entry.with_rule(rule.clone())).collect())
+        })
+        .collect::<io::Result<_>>()?;
+    Ok(planned.into_iter().flatten().collect())
+}
+
+fn is_zip_path(path: &Path) -> bool
+{
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Appends the extension of `compression` to `outfile` unless it already has
+/// an archive extension e.g. `-f source -c xz` writes `source.tar.xz`. The
+/// extension wins if both are passed.
+fn outfile_with_extension(outfile: &Path, compression: Option<Compression>) -> PathBuf
+{
+    let Some(compression) = compression
+    else
+    {
+        return outfile.to_path_buf();
+    };
+    match Compression::from_path(outfile)
+    {
+        Some(from_path) if from_path.to_extension() != compression.to_extension() =>
+        {
+            warn!(
+                "⚠️ Ignoring compression {} since {} has the file extension of {}.",
+                compression,
+                outfile.display(),
+                from_path
+            );
+            outfile.to_path_buf()
         }
-        Ok(())
-    })?;
-    Ok(())
+        Some(_) => outfile.to_path_buf(),
+        None if is_zip_path(outfile) =>
+        {
+            debug!(
+                "Ignoring compression {} since {} is a zip archive.",
+                compression,
+                outfile.display()
+            );
+            outfile.to_path_buf()
+        }
+        None =>
+        {
+            let mut outfile = outfile.as_os_str().to_os_string();
+            outfile.push(compression.to_extension());
+            PathBuf::from(outfile)
+        }
+    }
+}
+
+/// Formats an entry for `--dry-run` as `<type> <archive path> <- <source>
+/// (<rule>)` or as a JSON object.
+fn format_manifest_entry(entry: &ManifestEntry, json: bool) -> String
+{
+    let archive_path = entry.archive_path.to_string_lossy();
+    let source = entry.source.to_string_lossy();
+    if json
+    {
+        format!(
+            "{{\"path\":{},\"source\":{},\"type\":{},\"rule\":{},\"value\":{}}}",
+            json_string(&archive_path),
+            json_string(&source),
+            json_string(entry.kind.name()),
+            json_string(entry.rule.name()),
+            entry.rule.value().map_or("null".to_string(), json_string),
+        )
+    }
+    else
+    {
+        format!("{:<8} {} <- {} ({})", entry.kind.name(), archive_path, source, entry.rule)
+    }
+}
+
+/// Prints the planned entries to stdout for `--dry-run`.
+fn print_manifest(manifest: &Manifest, json: bool) -> Result<()>
+{
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    let res = manifest
+        .entries()
+        .try_for_each(|entry| writeln!(stdout, "{}", format_manifest_entry(entry, json)))
+        .and_then(|_| stdout.flush());
+    match res
+    {
+        // e.g. piped to `head`.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(Error::from),
+    }
+}
+
+/// Plans the archive of `roast_args` without writing anything. This resolves
+/// the target directory and applies its excluded, included and additional
+/// paths. Each entry records the rule that put it there. See `--dry-run`.
+pub fn plan_archive(roast_args: &RoastArgs) -> Result<Manifest>
+{
+    let target_path = process_globs(
+        roast_args.target.as_ref().ok_or("No target directory provided.").map_err(|err| {
+            error!(err);
+            Error::invalid_argument(err)
+        })?,
+    )?;
+    let target_path = target_path.canonicalize().unwrap_or(target_path);
+    // The archive is planned first and then streamed straight from the sources.
+    // `archive_root` is the path of the target directory in the archive.
+    let archive_root = if roast_args.preserve_root
+    {
+        PathBuf::from(target_path.file_name().unwrap_or_default())
+    }
+    else
+    {
+        PathBuf::new()
+    };
+    let mut manifest = Manifest::default();
+    if roast_args.preserve_root
+    {
+        manifest.insert(
+            ManifestEntry::new(&target_path, &archive_root, EntryKind::Dir)
+                .with_rule(EntryRule::PreserveRoot),
+        );
+    }
+
+    let excludes = PathMatcher::new(&target_path, &roast_args.exclude.clone().unwrap_or_default())?;
+    debug!(?excludes);
+
+    if let Some(additional_paths) = &roast_args.additional_paths
+    {
+        manifest.extend(process_additional_paths(
+            additional_paths,
+            &target_path,
+            &excludes,
+            &archive_root,
+            roast_args,
+        )?);
+    }
+
+    if let Some(include_paths) = &roast_args.include
+    {
+        // Plain paths are kept as they are. Patterns are expanded to the paths they
+        // match so that both follow the same precedence rules.
+        let (include_paths, include_patterns): (Vec<PathBuf>, Vec<PathBuf>) =
+            include_paths.iter().cloned().partition(|p| is_literal(&p.to_string_lossy()));
+        let includes = PathMatcher::new(&target_path, &include_patterns)?;
+        let matches =
+            includes.find_matches(&target_path, roast_args.ignore_hidden, roast_args.ignore_git)?;
+        if !include_patterns.is_empty() && matches.is_empty()
+        {
+            warn!("⚠️ INCLUDED patterns did not match anything: {:?}", include_patterns);
+        }
+        debug!(?include_paths, ?matches);
+        let include_paths: Vec<(PathBuf, String)> = include_paths
+            .into_iter()
+            .map(|p| {
+                let value = p.to_string_lossy().to_string();
+                (p, value)
+            })
+            .chain(matches.into_iter().map(|p| {
+                let pattern = includes.matched_pattern(&target_path.join(&p));
+                (p, pattern.unwrap_or_default())
+            }))
+            .collect();
+        manifest.extend(process_include_paths(
+            &include_paths,
+            &excludes,
+            &target_path,
+            &archive_root,
+            roast_args,
+        )?);
+    }
+
+    // Only the walk of the target directory is filtered. Included and added paths
+    // are kept unless they are empty directories.
+    let filters = EntryFilters::new(roast_args);
+    manifest.extend(filter_paths(
+        &target_path,
+        &archive_root,
+        &WalkOptions::new(roast_args, &excludes, &filters),
+        &IgnoreFiles::default(),
+    )?);
+    manifest.retain_empty_dirs();
+    filters.skip_empty_dirs(&mut manifest);
+    filters.log_summary();
+    warn_escaping_symlinks(&manifest, &archive_root);
+
+    Ok(manifest)
 }
 
 /// Processes CLI arguments that matches the fields in the `RoastArgs`
@@ -260,7 +468,7 @@ pub(crate) fn process_include_paths(
 /// important values that are passed down in this function. NOTE: Always pass
 /// `false` to the `start_trace` parameter if there is already a global tracing
 /// activated in the environment.
-pub fn roast_opts(roast_args: &RoastArgs, start_trace: bool) -> io::Result<()>
+pub fn roast_opts(roast_args: &RoastArgs, start_trace: bool) -> Result<()>
 {
     if let Some(ref subcommand) = roast_args.subcommands
     {
@@ -271,160 +479,135 @@ pub fn roast_opts(roast_args: &RoastArgs, start_trace: bool) -> io::Result<()>
             {
                 print_completions(*shell, &mut cmd);
             }
+            crate::operations::cli::Subcommands::TrainZstdDict { outfile, max_size, samples } =>
+            {
+                write_zstd_dict(samples, *max_size, outfile)?;
+            }
         }
     }
     else
     {
+        // `--outfile -` writes the archive to stdout so logs must go to stderr. So does
+        // `--dry-run`.
+        let to_stdout = roast_args.outfile.as_deref() == Some(Path::new("-"));
+        let logs_to_stderr = to_stdout || roast_args.dry_run;
         if cfg!(feature = "obs")
         {
             if start_trace
             {
-                start_tracing();
+                start_tracing(logs_to_stderr);
             }
         }
         else if !roast_args.silent && start_trace
         {
-            start_tracing();
+            start_tracing(logs_to_stderr);
         }
 
         info!("❤️‍🔥 Starting Roast.");
         debug!(?roast_args);
-        let target_path = process_globs(
-            roast_args.target.as_ref().ok_or("No target directory provided.").map_err(|err| {
-                error!(err);
-                io::Error::new(io::ErrorKind::InvalidInput, err)
-            })?,
-        )?;
-        let target_path = target_path.canonicalize().unwrap_or(target_path);
-        let tmp_binding = tempfile::Builder::new()
-            .prefix(".rooooooooooaaaaaaaasssst")
-            .rand_bytes(8)
-            .tempdir()
-            .inspect_err(|err| {
-                error!(?err, "Failed to create temporary directory");
-            })?;
+        let manifest = plan_archive(roast_args)?;
+        debug!(?manifest);
 
-        let workdir = &tmp_binding.path();
-        let setup_workdir = if roast_args.preserve_root
+        if roast_args.dry_run
         {
-            workdir.join(target_path.file_name().unwrap_or_default())
+            return print_manifest(&manifest, roast_args.json);
         }
-        else
-        {
-            workdir.to_path_buf()
-        };
-        fs::create_dir_all(&setup_workdir)?;
-
-        let outdir = match &roast_args.outdir
-        {
-            Some(v) => v,
-            None => &std::env::current_dir()?,
-        };
-
-        if !outdir.is_dir()
-        {
-            std::fs::create_dir_all(outdir)?;
-        }
-
-        let outpath = outdir.join(
-            roast_args.outfile.as_ref().ok_or("No outfile value provided.").map_err(|err| {
-                error!(err);
-                io::Error::new(io::ErrorKind::InvalidInput, err)
-            })?,
-        );
-        let outpath = outpath.canonicalize().unwrap_or(outpath);
-
-        let mut exclude_canonicalized_paths: Vec<PathBuf> =
-            roast_args.exclude.clone().unwrap_or_default();
-
-        exclude_canonicalized_paths = exclude_canonicalized_paths
-            .iter()
-            .map(|p| target_path.join(p).canonicalize().unwrap_or_default())
-            // NOTE: This is important. as unwrap_or_default contains at least one element of
-            // Path::from("") or a PathBuf::new()
-            .filter(|p| !p.to_string_lossy().trim().is_empty())
-            .collect();
-
-        debug!(?exclude_canonicalized_paths);
 
-        if let Some(additional_paths) = &roast_args.additional_paths
+        let outpath = if to_stdout
         {
-            process_additional_paths(
-                additional_paths,
-                &target_path,
-                &exclude_canonicalized_paths,
-                &setup_workdir,
-                roast_args,
-            )?;
+            None
         }
-
-        if let Some(include_paths) = &roast_args.include
+        else
         {
-            process_include_paths(
-                include_paths,
-                &exclude_canonicalized_paths,
-                &target_path,
-                &setup_workdir,
-                roast_args,
-            )?;
-        }
-
-        filter_paths(
-            &target_path,
-            &setup_workdir,
-            roast_args.ignore_hidden,
-            roast_args.ignore_git,
-            &exclude_canonicalized_paths,
-        )?;
+            let outdir = match &roast_args.outdir
+            {
+                Some(v) => v,
+                None => &std::env::current_dir()?,
+            };
 
-        let mut archive_files: Vec<PathBuf> = Vec::new();
-        get_all_files(&mut archive_files, workdir)?;
+            if !outdir.is_dir()
+            {
+                std::fs::create_dir_all(outdir)?;
+            }
 
-        debug!(?archive_files);
+            let outfile =
+                roast_args.outfile.as_ref().ok_or("No outfile value provided.").map_err(|err| {
+                    error!(err);
+                    Error::invalid_argument(err)
+                })?;
+            let outpath = outdir.join(outfile_with_extension(outfile, roast_args.compression));
+            Some(outpath.canonicalize().unwrap_or(outpath))
+        };
 
         let reproducible = roast_args.reproducible;
 
-        let outpath_str = outpath.as_os_str().to_string_lossy();
-        let result = if outpath_str.ends_with("tar.gz")
+        let outpath_str = match &outpath
         {
-            compress::targz(&outpath, workdir, &archive_files, reproducible)
-        }
-        else if outpath_str.ends_with("tar.xz")
-        {
-            compress::tarxz(&outpath, workdir, &archive_files, reproducible)
-        }
-        else if outpath_str.ends_with("tar.zst") | outpath_str.ends_with("tar.zstd")
-        {
-            compress::tarzst(&outpath, workdir, &archive_files, reproducible)
-        }
-        else if outpath_str.ends_with("tar.bz")
-        {
-            compress::tarbz2(&outpath, workdir, &archive_files, reproducible)
-        }
-        else if outpath_str.ends_with("tar")
-        {
-            compress::vanilla(&outpath, workdir, &archive_files, reproducible)
-        }
-        else
+            Some(outpath) => outpath.to_string_lossy(),
+            None => "stdout".into(),
+        };
+        // Zip archives are not tarballs so they have no `Compression`.
+        let is_zip = outpath.as_deref().is_some_and(is_zip_path);
+        let compression = match &outpath
         {
-            let msg = format!("Unsupported file: {}", outpath_str);
-            Err(io::Error::new(io::ErrorKind::Unsupported, msg))
+            Some(outpath) => Compression::from_path(outpath),
+            None => Some(roast_args.compression.unwrap_or_default()),
         };
-
-        // Do not return the error. Just inform the user.
-        // This will allow us to delete the temporary directory.
-        if let Err(err) = result
+        let zstd_dict = match (&roast_args.zstd_dict, compression)
         {
-            error!(?err);
-        }
-        else
+            (Some(dict), Some(Compression::Zst | Compression::Zstd)) =>
+            {
+                Some(fs::read(dict).inspect_err(|err| {
+                    error!(?err, "Unable to read zstd dictionary {}", dict.display());
+                })?)
+            }
+            (Some(dict), _) =>
+            {
+                warn!(
+                    "⚠️ Ignoring zstd dictionary {} since {} is not a zst tarball.",
+                    dict.display(),
+                    outpath_str
+                );
+                None
+            }
+            (None, _) => None,
+        };
+        let options = CodecOptions {
+            level: roast_args.level,
+            threads: roast_args.threads,
+            dictionary: zstd_dict.as_deref(),
+        };
+        let result = match (compression, &outpath)
         {
-            info!("🧑‍🍳 Your new tarball is now in {}", &outpath.display());
-        }
+            (None, Some(outpath)) if is_zip =>
+            {
+                compress::manifest_zip_archive(outpath, &manifest, reproducible, roast_args.level)
+            }
+            (Some(compression), Some(outpath)) =>
+            {
+                compress::manifest_tarball(compression, outpath, &manifest, reproducible, &options)
+            }
+            (Some(compression), None) => compress::manifest_tar_into(
+                io::BufWriter::new(io::stdout()),
+                compression,
+                &manifest,
+                reproducible,
+                &options,
+            )
+            .and_then(|mut stdout| stdout.flush()),
+            (None, _) => Err(Error::Format(UnsupportedFormat {
+                ext: outpath_str.to_string(),
+                mime_type: None,
+            })
+            .into()),
+        };
 
-        tmp_binding.close().inspect_err(|e| {
-            error!(?e, "Failed to delete temporary directory!");
-        })?;
+        result.inspect_err(|err| error!(?err))?;
+        info!(
+            "🧑‍🍳 Your new {} is now in {}",
+            if is_zip { "zip archive" } else { "tarball" },
+            outpath_str
+        );
     }
     Ok(())
 }
@@ -0,0 +1,249 @@
+// SPDX-License-Identifier: MPL-2.0
+
+// Copyright (C) 2025 Soc Virnyl Estela and contributors
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Gitignore-style patterns of `--exclude` and `--include`. Values without
+//! any pattern syntax are paths relative to the target directory. Also has
+//! the `.gitignore`, `.ignore` and `.roastignore` files of
+//! `--respect-ignore-files`.
+
+use super::helpers::is_hidden;
+use crate::error::Error;
+use ignore::{
+    Match,
+    gitignore::{
+        Gitignore,
+        GitignoreBuilder,
+    },
+};
+use std::{
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+};
+#[allow(unused_imports)]
+use tracing::{
+    Level,
+    debug,
+    error,
+    info,
+    trace,
+    warn,
+};
+
+/// Whether `value` is a plain path instead of a gitignore pattern. Plain paths
+/// have no glob characters, no `!` negation and no trailing `/`.
+pub(crate) fn is_literal(value: &str) -> bool
+{
+    !value.starts_with('!') && !value.ends_with('/') && !value.contains(['*', '?', '[', '\\'])
+}
+
+/// Turns a plain path into a gitignore pattern that only matches that path
+/// relative to `root`. Plain paths are anchored to `root` like before patterns
+/// were supported.
+fn anchored(value: &str, root: &Path) -> String
+{
+    let path = Path::new(value);
+    let path = path.strip_prefix(root).unwrap_or(path);
+    let path = path.to_string_lossy();
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+    format!("/{}", path)
+}
+
+#[derive(Debug, Clone)]
+/// Gitignore-style matcher of paths within `root`. Patterns are matched against
+/// the path relative to `root` so a pattern without a `/` e.g. `*.o` matches at
+/// any depth.
+pub(crate) struct PathMatcher
+{
+    root: PathBuf,
+    gitignore: Gitignore,
+}
+
+impl PathMatcher
+{
+    /// Builds a matcher from the values of `--exclude` or `--include`.
+    pub(crate) fn new(root: &Path, values: &[impl AsRef<Path>]) -> io::Result<Self>
+    {
+        let root = root.canonicalize().unwrap_or(root.to_path_buf());
+        let mut builder = GitignoreBuilder::new(&root);
+        for value in values
+        {
+            let value = value.as_ref().to_string_lossy();
+            let line = if is_literal(&value) { anchored(&value, &root) } else { value.to_string() };
+            debug!(?value, ?line, "Adding pattern");
+            builder
+                .add_line(None, &line)
+                .map_err(|err| invalid_pattern(&format!("Invalid pattern `{}`", value), err))?;
+        }
+        let gitignore = builder.build().map_err(|err| invalid_pattern("Invalid patterns", err))?;
+        Ok(PathMatcher { root, gitignore })
+    }
+
+    /// A matcher that matches nothing.
+    pub(crate) fn empty() -> Self
+    {
+        PathMatcher { root: PathBuf::new(), gitignore: Gitignore::empty() }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool { self.gitignore.is_empty() }
+
+    /// Whether `path` or one of its parents within `root` is matched. Paths
+    /// outside of `root` never match. Symlinks are matched as files.
+    pub(crate) fn is_match(&self, path: &Path) -> bool
+    {
+        let Some(relative) = self.relative(path)
+        else
+        {
+            return false;
+        };
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+        let matched = self.gitignore.matched_path_or_any_parents(relative, is_dir);
+        trace!(?path, ?matched);
+        matched.is_ignore()
+    }
+
+    /// Whether `path` itself is matched regardless of its parents.
+    pub(crate) fn is_match_exact(&self, path: &Path) -> bool
+    {
+        self.matched_pattern(path).is_some()
+    }
+
+    /// The pattern that matches `path` itself regardless of its parents.
+    pub(crate) fn matched_pattern(&self, path: &Path) -> Option<String>
+    {
+        let relative = self.relative(path)?;
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+        match self.gitignore.matched(relative, is_dir)
+        {
+            Match::Ignore(glob) => Some(glob.original().to_string()),
+            Match::None | Match::Whitelist(_) => None,
+        }
+    }
+
+    /// Paths of the entries below `dir` that are matched relative to the root
+    /// of the matcher.
+    /// Matched directories are not searched any further. Hidden entries are
+    /// skipped like in `super::helpers::filter_paths()`.
+    pub(crate) fn find_matches(
+        &self,
+        dir: &Path,
+        hidden: bool,
+        ignore_git: bool,
+    ) -> io::Result<Vec<PathBuf>>
+    {
+        let mut matches = Vec::new();
+        if self.is_empty()
+        {
+            return Ok(matches);
+        }
+        let mut pending = vec![dir.canonicalize().unwrap_or(dir.to_path_buf())];
+        while let Some(current) = pending.pop()
+        {
+            for entry in fs::read_dir(&current)?.flatten()
+            {
+                let path = entry.path();
+                if is_hidden(&path, hidden, ignore_git, dir)
+                {
+                    continue;
+                }
+                if self.is_match_exact(&path)
+                {
+                    matches.push(path.strip_prefix(&self.root).unwrap_or(&path).to_path_buf());
+                }
+                else if entry.file_type()?.is_dir()
+                {
+                    pending.push(path);
+                }
+            }
+        }
+        matches.sort();
+        Ok(matches)
+    }
+
+    fn relative<'p>(&self, path: &'p Path) -> Option<&'p Path>
+    {
+        if self.is_empty()
+        {
+            return None;
+        }
+        path.strip_prefix(&self.root).ok().filter(|relative| !relative.as_os_str().is_empty())
+    }
+}
+
+fn invalid_pattern(msg: &str, err: ignore::Error) -> io::Error
+{
+    error!(?err, msg);
+    Error::InvalidArgument { msg: format!("{}: {}", msg, err), source: Some(Box::new(err)) }.into()
+}
+
+/// Names of the ignore files that are read in each directory. Later files take
+/// precedence over earlier ones.
+pub(crate) const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".roastignore"];
+
+#[derive(Debug, Clone, Default)]
+/// The ignore files of a directory and of its parents. Deeper ignore files take
+/// precedence like in git. This is cheap to clone.
+pub(crate) struct IgnoreFiles
+{
+    matchers: Vec<Arc<Gitignore>>,
+}
+
+impl IgnoreFiles
+{
+    /// Returns a copy with the ignore files of `dir` added. Invalid lines are
+    /// skipped with a warning like git does.
+    pub(crate) fn with_dir(&self, dir: &Path) -> Self
+    {
+        let mut ignore_files = self.clone();
+        for file_name in IGNORE_FILES
+        {
+            let path = dir.join(file_name);
+            if !path.is_file()
+            {
+                continue;
+            }
+            let (gitignore, err) = Gitignore::new(&path);
+            if let Some(err) = err
+            {
+                warn!(?err, "⚠️ Skipping invalid lines of {}", path.display());
+            }
+            debug!(?path, "Using ignore file");
+            ignore_files.matchers.push(Arc::new(gitignore));
+        }
+        ignore_files
+    }
+
+    /// Whether `path` is ignored. Only the ignore files of its parents are
+    /// considered. Symlinks are matched as files.
+    pub(crate) fn is_ignored(&self, path: &Path) -> bool
+    {
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+        for gitignore in self.matchers.iter().rev()
+        {
+            if !path.starts_with(gitignore.path())
+            {
+                continue;
+            }
+            let matched = gitignore.matched(path, is_dir);
+            if matched.is_ignore()
+            {
+                trace!(?path, ?matched, "Ignored by an ignore file");
+                return true;
+            }
+            if matched.is_whitelist()
+            {
+                return false;
+            }
+        }
+        false
+    }
+}
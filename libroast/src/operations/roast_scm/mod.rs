@@ -1,6 +1,8 @@
 use crate::{
+    common::Compression,
+    error::{Error, Result},
     operations::{
-        cli::{RoastArgs, RoastScmArgs, print_completions},
+        cli::{RoastArgs, RoastScmArgs, print_completions, write_zstd_dict},
         roast::roast_opts,
     },
     utils::{copy_dir_all, start_tracing},
@@ -14,8 +16,8 @@ const CHANGELOG_LONG_SET_OF_DASHES: &str =
 const CHANGELOG_DATE_TIME_FORMAT: &str = "%a %b %d %H:%M:%S %T %Y";
 
 use git2::{
-    AutotagOption, Branch, BranchType, Commit, FetchOptions, Object, Oid, Repository, Submodule,
-    SubmoduleUpdateOptions, build::RepoBuilder,
+    AutotagOption, Branch, BranchType, Commit, ErrorClass, ErrorCode, FetchOptions, Object, Oid,
+    Repository, Submodule, SubmoduleUpdateOptions, build::RepoBuilder,
 };
 use regex::Regex;
 use std::{
@@ -139,7 +141,7 @@ fn update_repo_from_ref<'a>(
 
         local_repository.checkout_tree(&object, None).map_err(|err| {
             error!(?err);
-            io::Error::other(err.to_string())
+            io::Error::other(err)
         })?;
 
         match reference {
@@ -150,12 +152,17 @@ fn update_repo_from_ref<'a>(
         }
         .map_err(|err| {
             error!(?err);
-            io::Error::other(err.to_string())
+            io::Error::other(err)
         })?;
         Ok(object)
     } else {
         // Otherwise, we'll just return an error here.
-        Err(io::Error::other(format!("No revision `{}` found!", revision)))
+        Err(Error::Git(git2::Error::new(
+            ErrorCode::NotFound,
+            ErrorClass::Reference,
+            format!("No revision `{}` found!", revision),
+        ))
+        .into())
     }
 }
 
@@ -195,16 +202,16 @@ fn git_clone2(
     // builder.branch(revision);
     builder.clone(url, local_clone_dir).map_err(|err| {
         error!(?err);
-        io::Error::other(err.to_string())
+        io::Error::other(err)
     })?;
     let local_repository = Repository::open(local_clone_dir).map_err(|err| {
         error!(?err);
-        io::Error::other(err.to_string())
+        io::Error::other(err)
     })?;
 
     local_repository.cleanup_state().map_err(|err| {
         error!(?err);
-        io::Error::other(err.to_string())
+        io::Error::other(err)
     })?;
 
     let branch_type = BranchType::Remote;
@@ -268,7 +275,7 @@ fn git_clone2(
     // the index and tree.
     let mut submodules = local_repository.submodules().map_err(|err| {
         error!(?err);
-        io::Error::other(err.to_string())
+        io::Error::other(err)
     })?;
 
     submodules.iter_mut().try_for_each(|subm| update_submodule(&local_repository, subm))?;
@@ -702,7 +709,10 @@ fn mut_commit_msg(commit: &Commit, bulk_commit_message: &mut String) -> io::Resu
 fn process_basename_from_url(url_string: &str) -> io::Result<String> {
     let url = Url::parse(url_string).map_err(|err| {
         error!(?err);
-        io::Error::new(io::ErrorKind::InvalidInput, "Not able to parse URL string!")
+        Error::InvalidArgument {
+            msg: "Not able to parse URL string!".to_string(),
+            source: Some(Box::new(err)),
+        }
     })?;
     let path_segments = url
         .path_segments()
@@ -889,7 +899,7 @@ fn generate_changelog_file(
                 })?
             }
         } else {
-            return Err(io::Error::other("No changes author provided."));
+            return Err(Error::invalid_argument("No changes author provided.").into());
         }
     }
     Ok(())
@@ -996,34 +1006,39 @@ pub fn roast_scm_opts(
     custom_workdir: Option<PathBuf>,
     roast_scm_args: &RoastScmArgs,
     start_trace: bool,
-) -> io::Result<Option<std::path::PathBuf>> {
+) -> Result<Option<std::path::PathBuf>> {
     if let Some(ref subcommand) = roast_scm_args.subcommands {
         let mut cmd = RoastScmArgs::command();
         match subcommand {
             crate::operations::cli::Subcommands::GenerateCompletionsFor { shell } => {
                 print_completions(*shell, &mut cmd);
             }
+            crate::operations::cli::Subcommands::TrainZstdDict { outfile, max_size, samples } => {
+                write_zstd_dict(samples, *max_size, outfile)?;
+            }
         }
         Ok(None)
     } else {
         #[allow(clippy::if_same_then_else)] // It's not actually the same
         if cfg!(feature = "obs") {
             if start_trace {
-                start_tracing();
+                start_tracing(false);
             }
         } else if !roast_scm_args.silent && start_trace {
-            start_tracing();
+            start_tracing(false);
         }
         info!("⛓️🔥 Starting Roast SCM!");
         debug!(?roast_scm_args);
-        let workdir = if let Some(workdir) = custom_workdir {
-            workdir
+        // `_tmp_workdir` is a drop guard. It deletes the cloned repository when we
+        // return, including early returns from errors.
+        let (workdir, _tmp_workdir) = if let Some(workdir) = custom_workdir {
+            (workdir, None)
         } else {
             let tmp_workdir = tempfile::TempDir::new()?;
             if !roast_scm_args.is_temporary {
-                tmp_workdir.keep()
+                (tmp_workdir.keep(), None)
             } else {
-                tmp_workdir.path().to_owned()
+                (tmp_workdir.path().to_owned(), Some(tmp_workdir))
             }
         };
 
@@ -1033,7 +1048,7 @@ pub fn roast_scm_opts(
             .ok_or("No revision provided.")
             .map_err(|err| {
                 error!(err);
-                io::Error::new(io::ErrorKind::InvalidInput, err)
+                Error::invalid_argument(err)
             })?
             .to_string();
 
@@ -1043,7 +1058,7 @@ pub fn roast_scm_opts(
             .ok_or("No URL provided.")
             .map_err(|err| {
                 error!(err);
-                io::Error::new(io::ErrorKind::InvalidInput, err)
+                Error::invalid_argument(err)
             })?
             .to_string();
 
@@ -1080,53 +1095,64 @@ pub fn roast_scm_opts(
                 Path::new(&full_filename).to_path_buf()
             }
         };
+        // An outfile without a tarball extension gets the extension of the
+        // compression appended by `roast_opts()`.
+        let compression =
+            Compression::from_path(&outfile).is_none().then_some(roast_scm_args.compression);
         info!(?git_url, "🫂 Finished cloning remote repository.");
         info!("🍄 Cloned to `{}`.", &workdir.display());
 
         let roast_args = RoastArgs {
             target: Some(local_copy_dir),
-            include: None,
+            include: roast_scm_args.include.clone(),
             exclude: roast_scm_args.exclude.clone(),
             additional_paths: None,
             outfile: Some(outfile),
             outdir: roast_scm_args.outdir.clone(),
+            compression,
             preserve_root: true,
             reproducible: roast_scm_args.reproducible,
             ignore_git: roast_scm_args.ignore_git,
             ignore_hidden: roast_scm_args.ignore_hidden,
+            dereference: roast_scm_args.dereference,
+            respect_ignore_files: roast_scm_args.respect_ignore_files,
+            max_file_size: roast_scm_args.max_file_size,
+            exclude_type: roast_scm_args.exclude_type.clone(),
+            exclude_older_than: roast_scm_args.exclude_older_than,
+            exclude_newer_than: roast_scm_args.exclude_newer_than,
+            level: roast_scm_args.level,
+            threads: roast_scm_args.threads,
+            zstd_dict: roast_scm_args.zstd_dict.clone(),
+            dry_run: false,
+            json: false,
             silent: roast_scm_args.silent,
             subcommands: None,
         };
 
-        roast_opts(&roast_args, false)
-            .map(|_| {
-                generate_changelog_file(
-                    roast_scm_args,
-                    &changelog_details,
-                    &final_revision_format,
-                )?;
-
-                if cfg!(feature = "obs") {
-                    set_version_in_specfile(&roast_scm_args.set_version, &final_revision_format)?;
-                    produce_roast_info(
-                        &roast_scm_args.outdir.clone().unwrap_or(std::env::current_dir()?),
-                        &changelog_details.commit_hash,
-                    )?;
-                }
+        // Do not touch the changelog and specfile if there is no archive.
+        roast_opts(&roast_args, false).inspect_err(|err| {
+            error!(?err, "Unable to create the archive. Skipping changelog and specfile updates.");
+        })?;
 
-                if !roast_scm_args.is_temporary {
-                    info!(
-                        "👁️ Locally cloned repository is not deleted and located at `{}`.",
-                        workdir.display()
-                    );
-                    Ok(Some(workdir.to_path_buf()))
-                } else {
-                    Ok(None)
-                }
-            })
-            .inspect_err(|err| {
-                error!(?err);
-            })?
-            .inspect(|_| info!("⛓️🔥 Finished Roast SCM!"))
+        generate_changelog_file(roast_scm_args, &changelog_details, &final_revision_format)?;
+
+        if cfg!(feature = "obs") {
+            set_version_in_specfile(&roast_scm_args.set_version, &final_revision_format)?;
+            produce_roast_info(
+                &roast_scm_args.outdir.clone().unwrap_or(std::env::current_dir()?),
+                &changelog_details.commit_hash,
+            )?;
+        }
+
+        info!("⛓️🔥 Finished Roast SCM!");
+        if !roast_scm_args.is_temporary {
+            info!(
+                "👁️ Locally cloned repository is not deleted and located at `{}`.",
+                workdir.display()
+            );
+            Ok(Some(workdir.to_path_buf()))
+        } else {
+            Ok(None)
+        }
     }
 }
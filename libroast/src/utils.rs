@@ -6,25 +6,22 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 use crate::{
+    Error,
     common::{
         Compression,
+        InputFormat,
         SupportedFormat,
         UnsupportedFormat,
     },
-    consts::{
-        BZ2_MIME,
-        GZ_MIME,
-        SUPPORTED_MIME_TYPES,
-        TAR_MIME,
-        XZ_MIME,
-        ZST_MIME,
-    },
 };
 use glob::glob;
 use rayon::prelude::*;
 use std::{
     fs,
-    io,
+    io::{
+        self,
+        Read,
+    },
     path::{
         Path,
         PathBuf,
@@ -42,11 +39,16 @@ use tracing::{
     trace,
     warn,
 };
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{
+    EnvFilter,
+    fmt::writer::BoxMakeWriter,
+};
 
 /// Utility function to start tracing subscriber in the environment for logging.
 /// Supports coloured and no coloured outputs using `terminfo::capability`.
-pub(crate) fn start_tracing()
+/// Logs are written to stderr if `to_stderr` is set e.g. when the archive
+/// itself is written to stdout.
+pub(crate) fn start_tracing(to_stderr: bool)
 {
     let terminfodb = Database::from_env().map_err(|e| {
         error!(err = ?e, "Unable to access terminfo db. This is a bug!");
@@ -66,7 +68,10 @@ pub(crate) fn start_tracing()
         .with_level(true)
         .with_ansi(is_termcolorsupported)
         .with_env_filter(filter_layer)
-        .with_level(true);
+        .with_level(true)
+        .with_writer(
+            if to_stderr { BoxMakeWriter::new(io::stderr) } else { BoxMakeWriter::new(io::stdout) },
+        );
 
     let builder = if cfg!(debug_assertions)
     {
@@ -80,58 +85,307 @@ pub(crate) fn start_tracing()
     builder.init();
 }
 
-/// Checks if a valid file has a supported filetype regardless of extension
-/// using `infer::get_from_path()`. Fallback uses the file extension if a file
-/// extension is known to be supported and the usual mime-type.
-pub fn is_supported_format(src: &Path) -> Result<SupportedFormat, UnsupportedFormat>
+/// Determines the number of threads that compressors should use if the user
+/// did not set one. `std::thread::available_parallelism()` already takes the
+/// CPU quota of the cgroup the process is running in into account on Linux so
+/// shared workers and containers are not oversubscribed.
+pub fn available_threads() -> u32
+{
+    let threads = std::thread::available_parallelism()
+        .map(|threads| u32::try_from(threads.get()).unwrap_or(u32::MAX))
+        .unwrap_or(1);
+    debug!(?threads, "Number of threads available");
+    threads
+}
+
+/// Returns the number of threads to use. A value of `0` means the value is
+/// decided automatically with `available_threads()`.
+pub fn resolve_threads(threads: u32) -> u32
+{
+    if threads == 0 { available_threads() } else { threads }
+}
+
+/// Quotes and escapes `s` as a JSON string.
+pub(crate) fn json_string(s: &str) -> String
 {
-    if let Ok(identified_src) = infer::get_from_path(src)
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars()
     {
-        if let Some(known) = identified_src
+        match c
         {
-            debug!(?known);
-            if SUPPORTED_MIME_TYPES.contains(&known.mime_type())
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Number of bytes read to detect the format of an archive. Uncompressed
+/// tarballs have their magic bytes at offset 257 and their header checksum
+/// covers the first 512 bytes.
+pub const MAGIC_PEEK_SIZE: usize = 512;
+
+/// Offset of the ustar magic in a tar header.
+const TAR_MAGIC_OFFSET: usize = 257;
+/// Offset and length of the checksum field in a tar header.
+const TAR_CHECKSUM_OFFSET: usize = 148;
+const TAR_CHECKSUM_LEN: usize = 8;
+/// Size of a tar header.
+const TAR_HEADER_SIZE: usize = 512;
+
+/// Checks if `buf` starts with a tar header. Ustar, GNU and pax headers have
+/// a magic. Old v7 headers have none so their checksum is verified instead.
+fn is_tar_header(buf: &[u8]) -> bool
+{
+    let Some(header) = buf.get(..TAR_HEADER_SIZE)
+    else
+    {
+        return false;
+    };
+    // POSIX ustar and pax use "ustar\000", GNU tar uses "ustar  \0".
+    let magic = &header[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 8];
+    if magic == b"ustar\x0000" || magic == b"ustar  \x00"
+    {
+        return true;
+    }
+    // An empty block is the end of an archive and not a header.
+    if header.iter().all(|b| *b == 0)
+    {
+        return false;
+    }
+    let field = &header[TAR_CHECKSUM_OFFSET..TAR_CHECKSUM_OFFSET + TAR_CHECKSUM_LEN];
+    let Some(expected) = std::str::from_utf8(field)
+        .ok()
+        .map(|field| field.trim_matches(|c: char| c == '\0' || c == ' '))
+        .and_then(|field| u32::from_str_radix(field, 8).ok())
+    else
+    {
+        return false;
+    };
+    // The checksum is computed with the checksum field filled with spaces. Some
+    // old archivers summed signed bytes.
+    let (unsigned, signed) =
+        header.iter().enumerate().fold((0u32, 0i32), |(unsigned, signed), (i, b)| {
+            let b = if (TAR_CHECKSUM_OFFSET..TAR_CHECKSUM_OFFSET + TAR_CHECKSUM_LEN).contains(&i)
             {
-                return if known.mime_type().eq(GZ_MIME)
-                {
-                    Ok(SupportedFormat::Compressed(Compression::Gz, src.to_path_buf()))
-                }
-                else if known.mime_type().eq(XZ_MIME)
-                {
-                    Ok(SupportedFormat::Compressed(Compression::Xz, src.to_path_buf()))
-                }
-                else if known.mime_type().eq(ZST_MIME)
-                {
-                    Ok(SupportedFormat::Compressed(Compression::Zst, src.to_path_buf()))
-                }
-                else if known.mime_type().eq(BZ2_MIME)
-                {
-                    Ok(SupportedFormat::Compressed(Compression::Bz2, src.to_path_buf()))
-                }
-                else if known.mime_type().eq(TAR_MIME)
-                {
-                    Ok(SupportedFormat::Compressed(Compression::Not, src.to_path_buf()))
-                }
-                else
-                {
-                    error!("Should not be able to reach here!");
-                    unreachable!()
-                };
+                b' '
             }
-        }
-        else
-        {
-            let get_ext = match src.extension()
+            else
             {
-                Some(ext) => ext.to_string_lossy().to_string(),
-                None => "unknown format".to_string(),
+                *b
             };
-            return Err(UnsupportedFormat { ext: get_ext });
+            (unsigned + u32::from(b), signed + i32::from(b as i8))
+        });
+    expected == unsigned || i32::try_from(expected).is_ok_and(|expected| expected == signed)
+}
+
+/// Checks if `buf` starts with a legacy lzma-alone header. These have no magic
+/// so the properties, the dictionary size and the uncompressed size are
+/// checked for plausible values.
+fn is_lzma_header(buf: &[u8]) -> bool
+{
+    let Some(header) = buf.get(..13)
+    else
+    {
+        return false;
+    };
+    // lc + lp * 9 + pb * 45 with lc <= 8, lp <= 4 and pb <= 4.
+    if header[0] > 224
+    {
+        return false;
+    }
+    let dict_size = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+    // Encoders write either 2^n or 2^n + 2^(n-1).
+    let plausible_dict = dict_size.is_power_of_two()
+        || (dict_size >> dict_size.trailing_zeros() == 3 && dict_size.trailing_zeros() > 0);
+    let mut size = [0u8; 8];
+    size.copy_from_slice(&header[5..13]);
+    let size = u64::from_le_bytes(size);
+    // The size is unknown i.e. `u64::MAX` when streamed. Otherwise, it is
+    // unlikely to be larger than 256 GiB.
+    plausible_dict && (size == u64::MAX || size < 1 << 38)
+}
+
+/// Detects the format of an archive from its first bytes. Pass at least
+/// `MAGIC_PEEK_SIZE` bytes to detect uncompressed tarballs. Brotli has no
+/// magic bytes so it is never detected. Legacy lzma has none either and is
+/// only detected from plausible header values.
+pub fn sniff_format(buf: &[u8]) -> Option<InputFormat>
+{
+    let format = match buf
+    {
+        [0x1f, 0x8b, ..] => InputFormat::Gz,
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => InputFormat::Xz,
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => InputFormat::Zst,
+        // Zstd skippable frames e.g. written by `pzstd`.
+        [0x50..=0x5f, 0x2a, 0x4d, 0x18, ..] => InputFormat::Zst,
+        [b'B', b'Z', b'h', b'1'..=b'9', ..] => InputFormat::Bz2,
+        // Frame format and the legacy format of `lz4 -l`.
+        [0x04, 0x22, 0x4d, 0x18, ..] | [0x02, 0x21, 0x4c, 0x18, ..] => InputFormat::Lz4,
+        [b'L', b'Z', b'I', b'P', ..] => InputFormat::Lz,
+        [b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c, ..] => InputFormat::SevenZ,
+        // Local file header, empty archive and spanned archive.
+        [b'P', b'K', 0x03, 0x04, ..]
+        | [b'P', b'K', 0x05, 0x06, ..]
+        | [b'P', b'K', 0x07, 0x08, ..] => InputFormat::Zip,
+        _ if is_tar_header(buf) => InputFormat::Tar,
+        _ if is_lzma_header(buf) => InputFormat::Lzma,
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// Guesses the format of an archive from its file name e.g. `.tar.gz` or
+/// `.zip`. Only used to fill the gaps of `sniff_format()` and to warn about
+/// misleading file extensions.
+fn format_from_file_name(src: &Path) -> Option<InputFormat>
+{
+    if let Some(compression) = Compression::from_path(src)
+    {
+        return Some(compression.into());
+    }
+    let ext = src.extension()?.to_string_lossy().to_ascii_lowercase();
+    match ext.as_str()
+    {
+        // Brotli has no magic bytes so this is all we have to go on.
+        "br" => Some(InputFormat::Br),
+        "zip" => Some(InputFormat::Zip),
+        "7z" => Some(InputFormat::SevenZ),
+        _ => None,
+    }
+}
+
+/// Describes what `infer` makes of `buf` for errors e.g. `image/png`.
+fn infer_mime_type(buf: &[u8]) -> String
+{
+    infer::get(buf).map_or("unknown".to_string(), |known| {
+        debug!(?known);
+        known.mime_type().to_string()
+    })
+}
+
+/// Detects the compression of a tarball from its first bytes with
+/// `sniff_format()`. Useful for streams such as stdin that have no file name.
+/// Zip and 7z archives need to seek so they are rejected here. Brotli and
+/// legacy lzma are not reliably detected. Pass the compression explicitly
+/// for these.
+pub fn detect_compression(buf: &[u8]) -> Result<Compression, UnsupportedFormat>
+{
+    match sniff_format(buf).map(|format| (format, format.to_supported_format(PathBuf::new())))
+    {
+        Some((_, SupportedFormat::Compressed(compression, _))) => Ok(compression),
+        Some((format, _)) => Err(UnsupportedFormat {
+            ext: format!("{} archives cannot be streamed", format),
+            mime_type: Some(format.mime_type().to_string()),
+        }),
+        None => Err(UnsupportedFormat {
+            ext: "unknown format".to_string(),
+            mime_type: Some(infer_mime_type(buf)),
+        }),
+    }
+}
+
+/// Checks if a valid file has a supported format regardless of extension with
+/// `sniff_format()`. Falls back to the file extension for brotli, which has
+/// no magic bytes. If the magic bytes and the file extension disagree, the
+/// magic bytes win with a warning.
+pub fn is_supported_format(src: &Path) -> Result<SupportedFormat, UnsupportedFormat>
+{
+    let ext = src.extension().map(|ext| ext.to_string_lossy().to_string());
+    let mut buf: Vec<u8> = Vec::new();
+    let read = fs::File::open(src)
+        .and_then(|file| file.take(MAGIC_PEEK_SIZE as u64).read_to_end(&mut buf));
+    if let Err(err) = read
+    {
+        error!(?err, ?src, "Unable to read magic bytes");
+        return Err(UnsupportedFormat {
+            ext: ext.unwrap_or("no file extension".to_string()),
+            mime_type: None,
+        });
+    }
+    let sniffed = sniff_format(&buf);
+    let from_file_name = format_from_file_name(src);
+    debug!(?sniffed, ?from_file_name);
+    let format = match (sniffed, from_file_name)
+    {
+        // The lzma header values are only plausible and brotli streams might look
+        // like anything.
+        (Some(InputFormat::Lzma), Some(InputFormat::Br)) | (None, Some(InputFormat::Br)) =>
+        {
+            debug!(?src, "Assuming brotli from file extension");
+            InputFormat::Br
+        }
+        (Some(sniffed), Some(from_file_name)) if sniffed != from_file_name =>
+        {
+            warn!(
+                "⚠️ {} looks like {} from its magic bytes but its file extension suggests {}. \
+                 Using {}. Pass `--format` to override.",
+                src.display(),
+                sniffed,
+                from_file_name,
+                sniffed
+            );
+            sniffed
         }
+        (Some(sniffed), _) => sniffed,
+        (None, _) =>
+        {
+            return Err(UnsupportedFormat {
+                ext: ext.unwrap_or("no file extension".to_string()),
+                mime_type: Some(infer_mime_type(&buf)),
+            });
+        }
+    };
+    Ok(format.to_supported_format(src.to_path_buf()))
+}
+
+/// Canonicalizes `path`. Symlinks are followed only if `dereference` is set.
+/// Otherwise, only the parent of a symlink is canonicalized so that the
+/// symlink itself is kept.
+pub(crate) fn canonicalize_entry(path: &Path, dereference: bool) -> PathBuf
+{
+    if !dereference && path.is_symlink()
+    {
+        if let (Some(parent), Some(file_name)) = (path.parent(), path.file_name())
+        {
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            return parent.canonicalize().unwrap_or(parent.to_path_buf()).join(file_name);
+        }
+        return path.to_path_buf();
     }
-    Err(UnsupportedFormat { ext: "unknown format".to_string() })
+    path.canonicalize().unwrap_or(path.to_path_buf())
 }
 
+/// Recreates the symlink `src` at `dst` with the same target. An existing file
+/// at `dst` is replaced. Platforms without symlinks get a copy of the target
+/// instead.
+pub(crate) fn copy_symlink(src: &Path, dst: &Path) -> io::Result<()>
+{
+    if dst.symlink_metadata().is_ok()
+    {
+        fs::remove_file(dst)?;
+    }
+    #[cfg(unix)]
+    {
+        let target = fs::read_link(src)?;
+        trace!(?src, ?target, "Copying symlink");
+        std::os::unix::fs::symlink(target, dst)
+    }
+    #[cfg(not(unix))]
+    {
+        fs::copy(src, dst).map(|_| ())
+    }
+}
+
+/// Copies the contents of `src` into `dst` recursively. Symlinks are copied as
+/// symlinks.
 pub fn copy_dir_all(src: impl AsRef<Path>, dst: &Path) -> Result<(), io::Error>
 {
     debug!("Copying sources");
@@ -147,7 +401,12 @@ pub fn copy_dir_all(src: impl AsRef<Path>, dst: &Path) -> Result<(), io::Error>
         let ty = entry.file_type()?;
         trace!(?entry);
         trace!(?ty);
-        if ty.is_dir()
+        if ty.is_symlink()
+        {
+            trace!(?ty, "Is symlink?");
+            copy_symlink(&entry.path(), &dst.join(entry.file_name()))
+        }
+        else if ty.is_dir()
         {
             trace!(?ty, "Is directory?");
             let inner_dir = entry.path();
@@ -156,21 +415,6 @@ pub fn copy_dir_all(src: impl AsRef<Path>, dst: &Path) -> Result<(), io::Error>
                 inner_dir
             });
             copy_dir_all(&inner_dir, &dst.join(entry.file_name()))
-
-        // Should we respect symlinks?
-        // } else if ty.is_symlink() {
-        //     debug!("Is symlink");
-        //     let path = fs::read_link(&entry.path())?;
-        //     let path = fs::canonicalize(&path).unwrap();
-        //     debug!(?path);
-        //     let pathfilename = path.file_name().unwrap_or(OsStr::new("."));
-        //     if path.is_dir() {
-        //         copy_dir_all(&path, &dst.join(pathfilename))?;
-        //     } else {
-        //         fs::copy(&path, &mut dst.join(pathfilename))?;
-        //     }
-
-        // Be pedantic or you get symlink error
         }
         else if ty.is_file()
         {
@@ -209,13 +453,17 @@ pub fn process_globs(src: &Path) -> io::Result<PathBuf>
         Err(e) =>
         {
             error!(err = ?e, "Invalid glob input");
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid glob input"));
+            return Err(Error::InvalidArgument {
+                msg: "Invalid glob input".to_string(),
+                source: Some(Box::new(e)),
+            }
+            .into());
         }
     };
 
     let mut globs = glob_iter.into_iter().collect::<Result<Vec<_>, _>>().map_err(|e| {
         error!(?e, "glob error");
-        io::Error::new(io::ErrorKind::InvalidInput, "Glob error")
+        io::Error::from(Error::Filter { msg: "Glob error".to_string(), source: Some(Box::new(e)) })
     })?;
 
     // There can legitimately be multiple matching files. Generally this happens
@@ -237,6 +485,6 @@ pub fn process_globs(src: &Path) -> io::Result<PathBuf>
     // Take the last item.
     globs.pop().inspect(|item| info!("✅ Matched an item: {}", item.display())).ok_or_else(|| {
         error!("No files/directories matched src glob input");
-        io::Error::new(io::ErrorKind::InvalidInput, "No files/directories matched src glob input")
+        Error::filter("No files/directories matched src glob input").into()
     })
 }
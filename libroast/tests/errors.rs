@@ -0,0 +1,128 @@
+use clap::Parser;
+use libroast::{
+    Error,
+    operations::{
+        cli::{
+            RawArgs,
+            RecomprizzArgs,
+            RoastArgs,
+        },
+        raw::raw_opts,
+        recomprizz::recomprizz_opts,
+        roast::roast_opts,
+    },
+};
+use std::{
+    fs::create_dir_all,
+    io,
+    path::Path,
+};
+use test_log::test;
+#[allow(unused_imports)]
+use tracing::{
+    Level,
+    debug,
+    error,
+    info,
+    trace,
+    warn,
+};
+
+fn parse<P: Parser>(args: &[&str]) -> io::Result<P>
+{
+    P::try_parse_from(args)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))
+}
+
+/// Roasts a small source directory into `outdir` and returns the path of the
+/// tarball.
+fn roast_source(tmp: &Path, outdir: &Path) -> io::Result<std::path::PathBuf>
+{
+    let target = tmp.join("foo-1.0");
+    create_dir_all(target.join("src"))?;
+    std::fs::write(target.join("src/main.rs"), "fn main() {}")?;
+    let roast_args: RoastArgs = parse(&["roast", "-f", "foo-1.0.tar.gz"])?;
+    let roast_args =
+        RoastArgs { target: Some(target), outdir: Some(outdir.to_path_buf()), ..roast_args };
+    roast_opts(&roast_args, false)?;
+    Ok(outdir.join("foo-1.0.tar.gz"))
+}
+
+#[test]
+fn invalid_arguments_are_reported_as_such() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let outdir = tmp.path().join("out");
+    let tarball = roast_source(tmp.path(), &outdir)?;
+
+    // An invalid rename regex.
+    let recomprizz_args: RecomprizzArgs = parse(&[
+        "recomprizz",
+        "-t",
+        &tarball.to_string_lossy(),
+        "-d",
+        &outdir.to_string_lossy(),
+        "-R",
+        "foo-(",
+        "--renamepattern",
+        "bar",
+    ])?;
+    assert!(matches!(recomprizz_opts(recomprizz_args), Err(Error::InvalidArgument { .. })));
+
+    // A missing target.
+    let roast_args: RoastArgs = parse(&["roast", "-f", "foo-1.0.tar.gz"])?;
+    let roast_args = RoastArgs { outdir: Some(outdir.to_path_buf()), ..roast_args };
+    assert!(matches!(roast_opts(&roast_args, false), Err(Error::InvalidArgument { .. })));
+    let raw_args: RawArgs = parse(&["raw", "-d", &outdir.to_string_lossy()])?;
+    assert!(matches!(raw_opts(&raw_args, false), Err(Error::InvalidArgument { .. })));
+
+    // An invalid compression level.
+    let roast_args = RoastArgs {
+        target: Some(tmp.path().join("foo-1.0")),
+        outfile: Some("foo-1.0-level.tar.gz".into()),
+        level: Some(99),
+        ..roast_args
+    };
+    assert!(matches!(roast_opts(&roast_args, false), Err(Error::InvalidArgument { .. })));
+    assert!(!outdir.join("foo-1.0-level.tar.gz").exists());
+    Ok(())
+}
+
+#[test]
+fn unsupported_formats_are_reported_as_such() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("notes.txt");
+    std::fs::write(&target, "This is not an archive.")?;
+    let raw_args: RawArgs = parse(&[
+        "raw",
+        "-t",
+        &target.to_string_lossy(),
+        "-d",
+        &tmp.path().join("out").to_string_lossy(),
+    ])?;
+    assert!(matches!(raw_opts(&raw_args, false), Err(Error::Format(_))));
+    Ok(())
+}
+
+#[test]
+fn globs_without_a_match_are_reported_as_such() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let roast_args: RoastArgs = parse(&["roast", "-f", "foo-1.0.tar.gz"])?;
+    let roast_args = RoastArgs {
+        target: Some(tmp.path().join("foo-*")),
+        outdir: Some(tmp.path().join("out")),
+        ..roast_args
+    };
+    assert!(matches!(roast_opts(&roast_args, false), Err(Error::Filter { .. })));
+    let raw_args: RawArgs = parse(&[
+        "raw",
+        "-t",
+        &tmp.path().join("foo-*.tar.gz").to_string_lossy(),
+        "-d",
+        &tmp.path().join("out").to_string_lossy(),
+    ])?;
+    assert!(matches!(raw_opts(&raw_args, false), Err(Error::Filter { .. })));
+    Ok(())
+}
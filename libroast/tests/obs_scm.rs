@@ -58,6 +58,7 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_files() -> i
         set_name: None,
         git_repository_url: Some("https://github.com/openSUSE-Rust/obs-service-cargo".to_string()),
         exclude: None,
+        include: None,
         revision: Some("v5.1.0".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -68,7 +69,16 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_files() -> i
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r1, false)?;
     let copied_specfile_string = fs::read_to_string(&outdir.join("obs-service-cargo.spec"))?;
@@ -88,6 +98,7 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_files() -> i
         set_name: None,
         git_repository_url: Some("https://github.com/openSUSE-Rust/obs-service-cargo".to_string()),
         exclude: None,
+        include: None,
         revision: Some("2910335f66158a658dfeebca8b8bf6cfc09ba1c0".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -98,7 +109,16 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_files() -> i
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r2, false)?;
     let buf2 = read(outdir.join("r2.tar.zst"))?;
@@ -145,6 +165,7 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_filenames()
         set_name: None,
         git_repository_url: Some("https://github.com/openSUSE-Rust/obs-service-cargo".to_string()),
         exclude: None,
+        include: None,
         revision: Some("v5.1.0".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -155,7 +176,16 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_filenames()
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r1, false)?;
     std::env::set_current_dir(&outdir2)?;
@@ -170,6 +200,7 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_filenames()
         set_name: None,
         git_repository_url: Some("https://github.com/openSUSE-Rust/obs-service-cargo".to_string()),
         exclude: None,
+        include: None,
         revision: Some("2910335f66158a658dfeebca8b8bf6cfc09ba1c0".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -180,7 +211,16 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_filenames()
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r2, false)?;
     let read_dir1 = read_dir(outdir1)?;
@@ -1,6 +1,13 @@
-use libroast::operations::{
-    cli::RoastArgs,
-    roast::roast_opts,
+use clap::Parser;
+use libroast::{
+    manifest::EntryRule,
+    operations::{
+        cli::RoastArgs,
+        roast::{
+            plan_archive,
+            roast_opts,
+        },
+    },
 };
 use rayon::prelude::*;
 use sha3::{
@@ -14,7 +21,10 @@ use std::{
         create_dir_all,
         read,
     },
-    io,
+    io::{
+        self,
+        Read,
+    },
     path::{
         Path,
         PathBuf,
@@ -78,7 +88,7 @@ fn generate_gz_tarball(outpath: &Path) -> io::Result<()>
     libroast::utils::copy_dir_all(src, workdir)?;
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
-    libroast::compress::targz(outpath, workdir, &updated_paths, true)?;
+    libroast::compress::targz(outpath, workdir, &updated_paths, true, None)?;
     let res = libroast::utils::is_supported_format(outpath).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -96,7 +106,7 @@ fn generate_xz_tarball(outpath: &Path) -> io::Result<()>
     libroast::utils::copy_dir_all(src, workdir)?;
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
-    libroast::compress::tarxz(outpath, workdir, &updated_paths, true)?;
+    libroast::compress::tarxz(outpath, workdir, &updated_paths, true, None, 0)?;
     let res = libroast::utils::is_supported_format(outpath).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -114,7 +124,7 @@ fn generate_zst_tarball(outpath: &Path) -> io::Result<()>
     libroast::utils::copy_dir_all(src, workdir)?;
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
-    libroast::compress::tarzst(outpath, workdir, &updated_paths, true)?;
+    libroast::compress::tarzst(outpath, workdir, &updated_paths, true, None, 0, None)?;
     let res = libroast::utils::is_supported_format(outpath).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -132,7 +142,7 @@ fn generate_bz2_tarball(outpath: &Path) -> io::Result<()>
     libroast::utils::copy_dir_all(src, workdir)?;
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
-    libroast::compress::tarbz2(outpath, workdir, &updated_paths, true)?;
+    libroast::compress::tarbz2(outpath, workdir, &updated_paths, true, None)?;
     let res = libroast::utils::is_supported_format(outpath).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -150,7 +160,25 @@ fn generate_icecream_tarball(outpath: &Path) -> io::Result<()>
     libroast::utils::copy_dir_all(src, workdir)?;
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
-    libroast::compress::vanilla(outpath, workdir, &updated_paths, true)?;
+    libroast::compress::vanilla(outpath, workdir, &updated_paths, true, None)?;
+    let res = libroast::utils::is_supported_format(outpath).inspect_err(|err| error!(?err));
+    info!(?res);
+    assert!(res.is_ok());
+    Ok(())
+}
+
+fn generate_zip_archive(outpath: &Path) -> io::Result<()>
+{
+    let src = Path::new(MANIFEST_DIR).join("tests");
+    let tmp_binding = tempfile::TempDir::new().map_err(|err| {
+        error!(?err, "Failed to create temporary directory");
+        err
+    })?;
+    let workdir = &tmp_binding.path();
+    libroast::utils::copy_dir_all(src, workdir)?;
+    let mut updated_paths: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut updated_paths, workdir)?;
+    libroast::compress::zip_archive(outpath, workdir, &updated_paths, true, None)?;
     let res = libroast::utils::is_supported_format(outpath).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -252,6 +280,25 @@ fn repro_vanilla_tarball() -> io::Result<()>
     Ok(())
 }
 
+#[test]
+fn repro_zip_archive() -> io::Result<()>
+{
+    let mut hasher1 = Keccak256::new();
+    let mut hasher2 = Keccak256::new();
+    let outpath1 = Path::new("/tmp/ballszipperRepro1.zip");
+    generate_zip_archive(outpath1)?;
+    let outpath2 = Path::new("/tmp/ballszipperRepro2.zip");
+    generate_zip_archive(outpath2)?;
+    let buf1 = read(outpath1)?;
+    hasher1.update(buf1);
+    let hash1 = hasher1.finalize();
+    let buf2 = read(outpath2)?;
+    hasher2.update(buf2);
+    let hash2 = hasher2.finalize();
+    assert_eq!(hash1, hash2);
+    Ok(())
+}
+
 #[test]
 fn a_tree_of_empty_dirs_is_not_empty() -> io::Result<()>
 {
@@ -278,10 +325,22 @@ fn a_tree_of_empty_dirs_is_not_empty() -> io::Result<()>
         additional_paths: None,
         outfile: Some(outfile.to_path_buf()),
         outdir: None,
+        compression: None,
         preserve_root: false,
         reproducible: true,
         ignore_git: true,
         ignore_hidden: true,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
+        level: None,
+        threads: 0,
+        zstd_dict: None,
+        dry_run: false,
+        json: false,
         silent: false,
         subcommands: None,
     };
@@ -304,3 +363,659 @@ fn a_tree_of_empty_dirs_is_not_empty() -> io::Result<()>
 
     Ok(())
 }
+
+#[test]
+fn unsupported_outfile_is_an_error() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let outdir = tempfile::tempdir()?;
+
+    let roast_args = RoastArgs {
+        target: Some(tmp.path().to_path_buf()),
+        include: None,
+        exclude: None,
+        additional_paths: None,
+        outfile: Some(PathBuf::from("balls.rar")),
+        outdir: Some(outdir.path().to_path_buf()),
+        compression: None,
+        preserve_root: false,
+        reproducible: true,
+        ignore_git: true,
+        ignore_hidden: true,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
+        level: None,
+        threads: 0,
+        zstd_dict: None,
+        dry_run: false,
+        json: false,
+        silent: false,
+        subcommands: None,
+    };
+
+    assert!(matches!(roast_opts(&roast_args, false), Err(libroast::Error::Format(_))));
+    assert!(!outdir.path().join("balls.rar").exists());
+
+    let roast_args = RoastArgs { target: None, ..roast_args };
+    assert!(matches!(roast_opts(&roast_args, false), Err(libroast::Error::InvalidArgument { .. })));
+    Ok(())
+}
+
+#[test]
+fn zip_outfile_has_the_same_entries_as_a_tarball() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    create_dir_all(target.join("src"))?;
+    create_dir_all(target.join("target/debug"))?;
+    std::fs::write(target.join("Cargo.toml"), "[package]")?;
+    std::fs::write(target.join("src/main.rs"), "fn main() {}")?;
+    std::fs::write(target.join("target/debug/foo"), "ELF")?;
+    std::fs::write(target.join("target/debug/keep"), "keep")?;
+    let outdir = tempfile::tempdir()?;
+
+    let roast_args = RoastArgs {
+        target: Some(target.to_path_buf()),
+        include: Some(vec![PathBuf::from("target/debug/keep")]),
+        exclude: Some(vec![PathBuf::from("target")]),
+        additional_paths: None,
+        outfile: Some(PathBuf::from("foo-1.0.tar")),
+        outdir: Some(outdir.path().to_path_buf()),
+        compression: None,
+        preserve_root: true,
+        reproducible: true,
+        ignore_git: true,
+        ignore_hidden: true,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
+        level: None,
+        threads: 0,
+        zstd_dict: None,
+        dry_run: false,
+        json: false,
+        silent: false,
+        subcommands: None,
+    };
+    roast_opts(&roast_args, false)?;
+    let roast_args = RoastArgs { outfile: Some(PathBuf::from("foo-1.0.zip")), ..roast_args };
+    roast_opts(&roast_args, false)?;
+
+    let mut tar_entries: Vec<String> = Vec::new();
+    let mut ar = Archive::new(File::open(outdir.path().join("foo-1.0.tar"))?);
+    for entry in ar.entries()?
+    {
+        let entry = entry?;
+        let path = entry.path()?.to_string_lossy().trim_end_matches('/').to_string();
+        tar_entries.push(path);
+    }
+    let mut zip = zip::ZipArchive::new(File::open(outdir.path().join("foo-1.0.zip"))?)?;
+    let mut zip_entries: Vec<String> = Vec::new();
+    for index in 0..zip.len()
+    {
+        let file = zip.by_index(index)?;
+        assert_eq!(file.last_modified(), Some(zip::DateTime::default()));
+        zip_entries.push(file.name().trim_end_matches('/').to_string());
+    }
+    assert_eq!(tar_entries, zip_entries);
+    assert!(zip_entries.contains(&"foo-1.0/target/debug/keep".to_string()));
+    assert!(!zip_entries.contains(&"foo-1.0/target/debug/foo".to_string()));
+
+    let roast_args = RoastArgs { level: Some(10), ..roast_args };
+    assert!(matches!(roast_opts(&roast_args, false), Err(libroast::Error::InvalidArgument { .. })));
+    Ok(())
+}
+
+#[test]
+fn outfile_extension_aliases_pick_the_compression() -> io::Result<()>
+{
+    use libroast::common::Compression;
+    for (file_name, expected) in [
+        ("source.tar.gz", Some(Compression::Gz)),
+        ("source.TGZ", Some(Compression::Gz)),
+        ("source.txz", Some(Compression::Xz)),
+        ("source.tar.zstd", Some(Compression::Zst)),
+        ("source.tzst", Some(Compression::Zst)),
+        ("source.tar.bz", Some(Compression::Bz2)),
+        ("source.tbz2", Some(Compression::Bz2)),
+        ("source.tar.lz", Some(Compression::Lz)),
+        ("source.tlz", Some(Compression::Lzma)),
+        ("source.tar", Some(Compression::Not)),
+        ("source.zip", None),
+        ("sourcetar", None),
+        (".tar.gz", None),
+    ]
+    {
+        assert_eq!(Compression::from_path(file_name), expected, "{}", file_name);
+    }
+    assert_eq!(
+        Compression::split_extension("Source-1.0.TAR.XZ"),
+        Some(("Source-1.0", Compression::Xz))
+    );
+    assert_eq!(Compression::Bz2.to_extension(), ".tar.bz2");
+
+    let tmp = tempfile::tempdir()?;
+    std::fs::write(tmp.path().join("README"), "roast")?;
+    let outdir = tempfile::tempdir()?;
+    let roast_args = RoastArgs {
+        target: Some(tmp.path().to_path_buf()),
+        include: None,
+        exclude: None,
+        additional_paths: None,
+        outfile: Some(PathBuf::from("source")),
+        outdir: Some(outdir.path().to_path_buf()),
+        compression: Some(Compression::Xz),
+        preserve_root: false,
+        reproducible: true,
+        ignore_git: true,
+        ignore_hidden: true,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
+        level: None,
+        threads: 0,
+        zstd_dict: None,
+        dry_run: false,
+        json: false,
+        silent: false,
+        subcommands: None,
+    };
+    roast_opts(&roast_args, false)?;
+    assert!(matches!(
+        libroast::utils::is_supported_format(&outdir.path().join("source.tar.xz")),
+        Ok(libroast::common::SupportedFormat::Compressed(Compression::Xz, _))
+    ));
+
+    // The file extension wins over `--compression`.
+    let roast_args = RoastArgs { outfile: Some(PathBuf::from("source.tgz")), ..roast_args };
+    roast_opts(&roast_args, false)?;
+    assert!(matches!(
+        libroast::utils::is_supported_format(&outdir.path().join("source.tgz")),
+        Ok(libroast::common::SupportedFormat::Compressed(Compression::Gz, _))
+    ));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_are_kept_unless_dereferenced() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    create_dir_all(target.join("docs"))?;
+    std::fs::write(target.join("README"), "readme")?;
+    std::os::unix::fs::symlink("../README", target.join("docs/README"))?;
+    std::os::unix::fs::symlink("docs", target.join("doc"))?;
+    std::os::unix::fs::symlink("../outside", target.join("escaping"))?;
+    std::fs::write(tmp.path().join("outside"), "outside")?;
+    let outdir = tempfile::tempdir()?;
+
+    let roast_args = RoastArgs {
+        target: Some(target.to_path_buf()),
+        include: None,
+        exclude: None,
+        additional_paths: None,
+        outfile: Some(PathBuf::from("foo-1.0.tar")),
+        outdir: Some(outdir.path().to_path_buf()),
+        compression: None,
+        preserve_root: true,
+        reproducible: true,
+        ignore_git: true,
+        ignore_hidden: true,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
+        level: None,
+        threads: 0,
+        zstd_dict: None,
+        dry_run: false,
+        json: false,
+        silent: false,
+        subcommands: None,
+    };
+    roast_opts(&roast_args, false)?;
+    let mut links: Vec<(String, String)> = Vec::new();
+    let mut ar = Archive::new(File::open(outdir.path().join("foo-1.0.tar"))?);
+    for entry in ar.entries()?
+    {
+        let entry = entry?;
+        if entry.header().entry_type().is_symlink()
+        {
+            let link_name = entry.link_name()?.unwrap_or_default().to_string_lossy().to_string();
+            links.push((entry.path()?.to_string_lossy().to_string(), link_name));
+        }
+    }
+    links.sort();
+    assert_eq!(
+        links,
+        vec![
+            ("foo-1.0/doc".to_string(), "docs".to_string()),
+            ("foo-1.0/docs/README".to_string(), "../README".to_string()),
+            ("foo-1.0/escaping".to_string(), "../outside".to_string()),
+        ]
+    );
+
+    let roast_args = RoastArgs { dereference: true, ..roast_args };
+    roast_opts(&roast_args, false)?;
+    let mut files: Vec<String> = Vec::new();
+    let mut ar = Archive::new(File::open(outdir.path().join("foo-1.0.tar"))?);
+    for entry in ar.entries()?
+    {
+        let entry = entry?;
+        assert!(!entry.header().entry_type().is_symlink());
+        if entry.header().entry_type().is_file()
+        {
+            files.push(entry.path()?.to_string_lossy().to_string());
+        }
+    }
+    assert!(files.contains(&"foo-1.0/docs/README".to_string()));
+    assert!(files.contains(&"foo-1.0/doc/README".to_string()));
+    Ok(())
+}
+
+#[test]
+fn manifest_entries_are_streamed_from_their_sources() -> io::Result<()>
+{
+    use libroast::manifest::{
+        EntryKind,
+        Manifest,
+        ManifestEntry,
+    };
+    let tmp = tempfile::tempdir()?;
+    create_dir_all(tmp.path().join("src"))?;
+    create_dir_all(tmp.path().join("empty"))?;
+    std::fs::write(tmp.path().join("src/main.rs"), "fn main() {}")?;
+
+    let mut manifest = Manifest::default();
+    manifest.extend([
+        ManifestEntry::new(tmp.path().join("src"), "foo-1.0/src", EntryKind::Dir),
+        ManifestEntry::new(tmp.path().join("src/main.rs"), "foo-1.0/src/main.rs", EntryKind::File),
+        ManifestEntry::new(tmp.path().join("src/main.rs"), "foo-1.0/bin.rs", EntryKind::File),
+        ManifestEntry::new(tmp.path().join("empty"), "foo-1.0/empty", EntryKind::Dir),
+    ]);
+    manifest.retain_empty_dirs();
+    assert_eq!(manifest.len(), 3);
+
+    let tarball = libroast::compress::manifest_tar_into(
+        Vec::new(),
+        libroast::common::Compression::Not,
+        &manifest,
+        true,
+        &Default::default(),
+    )?;
+    let mut entries: Vec<(String, String)> = Vec::new();
+    let mut ar = Archive::new(tarball.as_slice());
+    for entry in ar.entries()?
+    {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let mut contents = String::new();
+        entry.read_to_string(&mut contents)?;
+        entries.push((path, contents));
+    }
+    assert_eq!(
+        entries,
+        vec![
+            ("foo-1.0/bin.rs".to_string(), "fn main() {}".to_string()),
+            ("foo-1.0/empty".to_string(), String::new()),
+            ("foo-1.0/src/main.rs".to_string(), "fn main() {}".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn exclude_and_include_accept_gitignore_patterns() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    for dir in ["src/nested", "target/debug", "vendor/target", "include"]
+    {
+        create_dir_all(target.join(dir))?;
+    }
+    for file in [
+        "Cargo.toml",
+        "src/main.rs",
+        "src/main.o",
+        "src/nested/lib.o",
+        "src/nested/lib.rs.orig",
+        "src/keep.orig",
+        "target/debug/foo",
+        "target/debug/foo.h",
+        "vendor/target/bar",
+        "include/foo.h",
+    ]
+    {
+        std::fs::write(target.join(file), file)?;
+    }
+    let outdir = tempfile::tempdir()?;
+
+    let roast_args = RoastArgs {
+        target: Some(target.to_path_buf()),
+        include: Some(vec![PathBuf::from("**/*.h")]),
+        exclude: Some(vec![
+            PathBuf::from("**/*.o"),
+            PathBuf::from("*.orig"),
+            PathBuf::from("!keep.orig"),
+            PathBuf::from("target/"),
+            PathBuf::from("include"),
+        ]),
+        additional_paths: None,
+        outfile: Some(PathBuf::from("foo-1.0.tar")),
+        outdir: Some(outdir.path().to_path_buf()),
+        compression: None,
+        preserve_root: false,
+        reproducible: true,
+        ignore_git: true,
+        ignore_hidden: true,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
+        level: None,
+        threads: 0,
+        zstd_dict: None,
+        dry_run: false,
+        json: false,
+        silent: false,
+        subcommands: None,
+    };
+    roast_opts(&roast_args, false)?;
+    let mut files: Vec<String> = Vec::new();
+    let mut ar = Archive::new(File::open(outdir.path().join("foo-1.0.tar"))?);
+    for entry in ar.entries()?
+    {
+        files.push(entry?.path()?.to_string_lossy().to_string());
+    }
+    assert_eq!(
+        files,
+        vec![
+            "Cargo.toml",
+            "include/foo.h",
+            "src/keep.orig",
+            "src/main.rs",
+            // Directories that are left empty are kept.
+            "src/nested",
+            "target/debug/foo.h",
+            "vendor",
+        ]
+    );
+
+    let roast_args = RoastArgs { exclude: Some(vec![PathBuf::from("[z-a]")]), ..roast_args };
+    assert!(matches!(roast_opts(&roast_args, false), Err(libroast::Error::InvalidArgument { .. })));
+    Ok(())
+}
+
+#[test]
+fn ignore_files_are_respected_when_asked() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    create_dir_all(target.join("src/generated"))?;
+    create_dir_all(target.join("target"))?;
+    std::fs::write(target.join(".gitignore"), "/target\n*.log\n")?;
+    std::fs::write(target.join(".roastignore"), "!keep.log\n")?;
+    std::fs::write(target.join("src/.ignore"), "generated/\n")?;
+    for file in
+        ["Cargo.toml", "build.log", "keep.log", "target/foo", "src/main.rs", "src/generated/bar.rs"]
+    {
+        std::fs::write(target.join(file), file)?;
+    }
+    let outdir = tempfile::tempdir()?;
+
+    let roast_args = RoastArgs {
+        target: Some(target.to_path_buf()),
+        include: None,
+        exclude: None,
+        additional_paths: None,
+        outfile: Some(PathBuf::from("foo-1.0.tar")),
+        outdir: Some(outdir.path().to_path_buf()),
+        compression: None,
+        preserve_root: false,
+        reproducible: true,
+        ignore_git: false,
+        ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: true,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
+        level: None,
+        threads: 0,
+        zstd_dict: None,
+        dry_run: false,
+        json: false,
+        silent: false,
+        subcommands: None,
+    };
+    let list = |roast_args: &RoastArgs| -> io::Result<Vec<String>> {
+        roast_opts(roast_args, false)?;
+        let mut files: Vec<String> = Vec::new();
+        let mut ar = Archive::new(File::open(outdir.path().join("foo-1.0.tar"))?);
+        for entry in ar.entries()?
+        {
+            files.push(entry?.path()?.to_string_lossy().to_string());
+        }
+        Ok(files)
+    };
+    assert_eq!(
+        list(&roast_args)?,
+        vec![".gitignore", ".roastignore", "Cargo.toml", "keep.log", "src/.ignore", "src/main.rs"]
+    );
+    let roast_args = RoastArgs { respect_ignore_files: false, ..roast_args };
+    assert_eq!(list(&roast_args)?.len(), 9);
+    Ok(())
+}
+
+#[test]
+fn entries_are_skipped_by_size_type_or_age() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    create_dir_all(target.join("empty"))?;
+    create_dir_all(target.join("tests/fixtures"))?;
+    std::fs::write(target.join("Cargo.toml"), "Cargo.toml")?;
+    std::fs::write(target.join("tests/fixtures/big.bin"), vec![0; 4096])?;
+    std::fs::write(target.join("old.txt"), "old.txt")?;
+    File::options()
+        .write(true)
+        .open(target.join("old.txt"))?
+        .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(946_684_800))?;
+    std::os::unix::fs::symlink("Cargo.toml", target.join("link"))?;
+    let outdir = tempfile::tempdir()?;
+
+    let list = |filters: &[&str]| -> io::Result<Vec<String>> {
+        let mut args = vec!["roast", "-f", "foo-1.0.tar", "--reproducible", "true"];
+        args.extend(filters);
+        let roast_args = RoastArgs::try_parse_from(args)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+        let roast_args = RoastArgs {
+            target: Some(target.to_path_buf()),
+            outdir: Some(outdir.path().to_path_buf()),
+            ..roast_args
+        };
+        roast_opts(&roast_args, false)?;
+        let mut files: Vec<String> = Vec::new();
+        let mut ar = Archive::new(File::open(outdir.path().join("foo-1.0.tar"))?);
+        for entry in ar.entries()?
+        {
+            files.push(entry?.path()?.to_string_lossy().to_string());
+        }
+        Ok(files)
+    };
+    assert_eq!(
+        list(&[])?,
+        vec!["Cargo.toml", "empty", "link", "old.txt", "tests/fixtures/big.bin"]
+    );
+    assert_eq!(
+        list(&["--max-file-size", "1024"])?,
+        vec!["Cargo.toml", "empty", "link", "old.txt", "tests/fixtures"]
+    );
+    assert_eq!(
+        list(&["--exclude-type", "symlink", "--exclude-type", "empty-dir"])?,
+        vec!["Cargo.toml", "old.txt", "tests/fixtures/big.bin"]
+    );
+    assert_eq!(
+        list(&["--exclude-older-than", "2010-01-01"])?,
+        vec!["Cargo.toml", "empty", "link", "tests/fixtures/big.bin"]
+    );
+    assert_eq!(
+        list(&["--exclude-newer-than", "2010-01-01T00:00:00Z"])?,
+        vec!["empty", "old.txt", "tests/fixtures"]
+    );
+    assert!(list(&["--exclude-older-than", "yesterday"]).is_err());
+    Ok(())
+}
+
+#[test]
+fn failed_archive_leaves_no_temporary_files() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    create_dir_all(target.join("src"))?;
+    std::fs::write(target.join("src/main.rs"), "fn main() {}")?;
+    let outdir = tmp.path().join("out");
+    // The tarball is written but cannot be moved over a directory.
+    create_dir_all(outdir.join("foo-1.0.tar.zst"))?;
+
+    let roast_args = RoastArgs::try_parse_from(["roast", "-f", "foo-1.0.tar.zst"])
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let roast_args = RoastArgs {
+        target: Some(target.to_path_buf()),
+        outdir: Some(outdir.to_path_buf()),
+        ..roast_args
+    };
+    assert!(roast_opts(&roast_args, false).is_err());
+    let leftovers: Vec<PathBuf> = std::fs::read_dir(&outdir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    assert_eq!(leftovers, vec![outdir.join("foo-1.0.tar.zst")]);
+    assert!(outdir.join("foo-1.0.tar.zst").is_dir());
+    Ok(())
+}
+
+#[test]
+fn dry_run_writes_nothing() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    create_dir_all(target.join("src"))?;
+    std::fs::write(target.join("src/main.rs"), "fn main() {}")?;
+    let outdir = tmp.path().join("out");
+
+    let roast_args = RoastArgs::try_parse_from(["roast", "-f", "foo-1.0.tar.zst", "--dry-run"])
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let roast_args = RoastArgs {
+        target: Some(target.to_path_buf()),
+        outdir: Some(outdir.to_path_buf()),
+        ..roast_args
+    };
+    roast_opts(&roast_args, false)?;
+    assert!(!outdir.exists());
+    roast_opts(&RoastArgs { json: true, ..roast_args }, false)?;
+    assert!(!outdir.exists());
+    assert!(RoastArgs::try_parse_from(["roast", "-f", "foo-1.0.tar.zst", "--json"]).is_err());
+    Ok(())
+}
+
+#[test]
+fn dry_run_shows_the_rule_of_each_entry() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    create_dir_all(target.join("src"))?;
+    create_dir_all(target.join("vendor/bar"))?;
+    for file in ["src/a.c", "src/b.h", "src/main.rs", "vendor/bar/lib.h"]
+    {
+        std::fs::write(target.join(file), file)?;
+    }
+    let extra = tmp.path().join("NOTICE");
+    std::fs::write(&extra, "NOTICE")?;
+
+    let roast_args = RoastArgs::try_parse_from([
+        "roast",
+        "-f",
+        "foo-1.0.tar.zst",
+        "--dry-run",
+        "-E",
+        "vendor",
+        "-i",
+        "src/a.c",
+        "-i",
+        "**/*.h",
+    ])
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let roast_args = RoastArgs {
+        target: Some(target.to_path_buf()),
+        additional_paths: Some(vec![format!("{},src", extra.display())]),
+        ..roast_args
+    };
+    let manifest = plan_archive(&roast_args).map_err(io::Error::from)?;
+    let rules: Vec<(String, EntryRule)> = manifest
+        .entries()
+        .map(|entry| (entry.archive_path.to_string_lossy().to_string(), entry.rule.clone()))
+        .collect();
+    assert_eq!(
+        rules,
+        vec![
+            (
+                "src/NOTICE".to_string(),
+                EntryRule::AdditionalPath(format!("{},src", extra.display()))
+            ),
+            ("src/a.c".to_string(), EntryRule::Include("src/a.c".to_string())),
+            ("src/b.h".to_string(), EntryRule::Include("**/*.h".to_string())),
+            ("src/main.rs".to_string(), EntryRule::Target),
+            ("vendor/bar/lib.h".to_string(), EntryRule::Include("**/*.h".to_string())),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn files_of_the_target_win_over_added_paths() -> io::Result<()>
+{
+    let tmp = tempfile::tempdir()?;
+    let target = tmp.path().join("foo-1.0");
+    create_dir_all(target.join("src"))?;
+    std::fs::write(target.join("src/NOTICE"), "target")?;
+    let extra = tmp.path().join("NOTICE");
+    std::fs::write(&extra, "added")?;
+
+    let roast_args = RoastArgs::try_parse_from(["roast", "-f", "foo-1.0.tar.zst", "--dry-run"])
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let roast_args = RoastArgs {
+        target: Some(target.to_path_buf()),
+        additional_paths: Some(vec![format!("{},src", extra.display())]),
+        ..roast_args
+    };
+    let manifest = plan_archive(&roast_args).map_err(io::Error::from)?;
+    let entries: Vec<(PathBuf, PathBuf, EntryRule)> = manifest
+        .entries()
+        .map(|entry| (entry.archive_path.clone(), entry.source.clone(), entry.rule.clone()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![(
+            PathBuf::from("src/NOTICE"),
+            target.canonicalize()?.join("src/NOTICE"),
+            EntryRule::Target
+        )]
+    );
+    Ok(())
+}
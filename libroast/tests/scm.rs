@@ -45,6 +45,7 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_files() -> i
         changesoutfile: None,
         git_repository_url: Some("https://github.com/openSUSE-Rust/obs-service-cargo".to_string()),
         exclude: None,
+        include: None,
         revision: Some("v5.1.0".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -55,8 +56,17 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_files() -> i
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         silent: false,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r1, false)?;
     let buf1 = read(outdir.join("r1.tar.zst"))?;
@@ -72,6 +82,7 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_files() -> i
         changesoutfile: None,
         git_repository_url: Some("https://github.com/openSUSE-Rust/obs-service-cargo".to_string()),
         exclude: None,
+        include: None,
         revision: Some("2910335f66158a658dfeebca8b8bf6cfc09ba1c0".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -82,7 +93,16 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_files() -> i
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r2, false)?;
     let buf2 = read(outdir.join("r2.tar.zst"))?;
@@ -117,6 +137,7 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_filenames()
         changesoutfile: None,
         git_repository_url: Some("https://github.com/openSUSE-Rust/obs-service-cargo".to_string()),
         exclude: None,
+        include: None,
         revision: Some("v5.1.0".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -127,7 +148,16 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_filenames()
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r1, false)?;
     let r2 = RoastScmArgs {
@@ -141,6 +171,7 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_filenames()
         changesoutfile: None,
         git_repository_url: Some("https://github.com/openSUSE-Rust/obs-service-cargo".to_string()),
         exclude: None,
+        include: None,
         revision: Some("2910335f66158a658dfeebca8b8bf6cfc09ba1c0".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -151,7 +182,16 @@ fn different_revisions_pointing_to_the_same_commit_produce_the_same_filenames()
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r2, false)?;
     let read_dir1 = read_dir(outdir1)?;
@@ -201,6 +241,7 @@ fn repo_with_submodules_1() -> io::Result<()>
         changesoutfile: None,
         git_repository_url: Some("https://codeberg.org/river/river".to_string()),
         exclude: None,
+        include: None,
         revision: Some("v0.2.2".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -211,7 +252,16 @@ fn repo_with_submodules_1() -> io::Result<()>
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r1, false)?;
     let r2 = RoastScmArgs {
@@ -225,6 +275,7 @@ fn repo_with_submodules_1() -> io::Result<()>
         changesoutfile: None,
         git_repository_url: Some("https://codeberg.org/river/river".to_string()),
         exclude: None,
+        include: None,
         revision: Some("v0.2.2".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -235,7 +286,16 @@ fn repo_with_submodules_1() -> io::Result<()>
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r2, false)?;
     let read_dir1 = read_dir(outdir1)?;
@@ -285,6 +345,7 @@ fn repo_with_submodules_2() -> io::Result<()>
         changesoutfile: None,
         git_repository_url: Some("https://github.com/mahkoh/jay".to_string()),
         exclude: None,
+        include: None,
         revision: Some("master".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -295,7 +356,16 @@ fn repo_with_submodules_2() -> io::Result<()>
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r1, false)?;
     let r2 = RoastScmArgs {
@@ -309,6 +379,7 @@ fn repo_with_submodules_2() -> io::Result<()>
         changesoutfile: None,
         git_repository_url: Some("https://github.com/mahkoh/jay".to_string()),
         exclude: None,
+        include: None,
         revision: Some("master".to_string()),
         versionrewriteregex: None,
         versionrewritepattern: None,
@@ -319,7 +390,16 @@ fn repo_with_submodules_2() -> io::Result<()>
         reproducible: true,
         ignore_git: true,
         ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
         compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
     };
     libroast::operations::roast_scm::roast_scm_opts(None, &r2, false)?;
     let read_dir1 = read_dir(outdir1)?;
@@ -342,3 +422,66 @@ fn repo_with_submodules_2() -> io::Result<()>
     assert_eq!(hasher1.finalize(), hasher2.finalize());
     Ok(())
 }
+
+#[test]
+fn failed_archive_skips_the_changelog() -> io::Result<()>
+{
+    let tmp_binding = tempfile::TempDir::new()?;
+    let upstream = tmp_binding.path().join("upstream");
+    let repository = git2::Repository::init(&upstream).map_err(io::Error::other)?;
+    std::fs::write(upstream.join("README"), "roast")?;
+    let mut index = repository.index().map_err(io::Error::other)?;
+    index.add_path(std::path::Path::new("README")).map_err(io::Error::other)?;
+    index.write().map_err(io::Error::other)?;
+    let tree = repository
+        .find_tree(index.write_tree().map_err(io::Error::other)?)
+        .map_err(io::Error::other)?;
+    let signature = git2::Signature::now("roast", "roast@example.com").map_err(io::Error::other)?;
+    let commit = repository
+        .commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])
+        .map_err(io::Error::other)?;
+    let commit = repository.find_object(commit, None).map_err(io::Error::other)?;
+    repository.tag_lightweight("v1.0.0", &commit, false).map_err(io::Error::other)?;
+
+    let outdir = tmp_binding.path().join("out");
+    // The tarball is written but cannot be moved over a directory.
+    std::fs::create_dir_all(outdir.join("upstream.tar.zst"))?;
+    let changesoutfile = tmp_binding.path().join("upstream.changes");
+    let roast_scm_args = RoastScmArgs {
+        subcommands: None,
+        set_version: None,
+        set_name: None,
+        changesgenerate: true,
+        changesauthor: Some("roast".to_string()),
+        changesemail: None,
+        changesoutfile: Some(changesoutfile.to_path_buf()),
+        git_repository_url: Some(format!("file://{}", upstream.display())),
+        exclude: None,
+        include: None,
+        revision: Some("v1.0.0".to_string()),
+        versionrewriteregex: None,
+        versionrewritepattern: None,
+        depth: 0,
+        is_temporary: true,
+        outfile: Some(PathBuf::from("upstream.tar.zst")),
+        outdir: Some(outdir.to_path_buf()),
+        reproducible: true,
+        ignore_git: true,
+        ignore_hidden: false,
+        dereference: false,
+        respect_ignore_files: false,
+        max_file_size: None,
+        exclude_type: None,
+        exclude_older_than: None,
+        exclude_newer_than: None,
+        silent: false,
+        compression: libroast::common::Compression::default(),
+        level: None,
+        threads: 0,
+        zstd_dict: None,
+    };
+    assert!(libroast::operations::roast_scm::roast_scm_opts(None, &roast_scm_args, false).is_err());
+    assert!(!changesoutfile.exists());
+    assert_eq!(read_dir(&outdir)?.count(), 1);
+    Ok(())
+}
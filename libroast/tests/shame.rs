@@ -1,6 +1,9 @@
 use rayon::prelude::*;
 use std::{
-    io,
+    io::{
+        self,
+        Write,
+    },
     path::{
         Path,
         PathBuf,
@@ -78,7 +81,7 @@ fn is_gz_tarball() -> io::Result<()>
     //     .filter(|p| p.is_file())
     //     .collect();
     let out = Path::new("/tmp/ballsofDeezNuts");
-    libroast::compress::targz(out, workdir, &updated_paths, true)?;
+    libroast::compress::targz(out, workdir, &updated_paths, true, None)?;
     let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -98,7 +101,7 @@ fn is_xz_tarball() -> io::Result<()>
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
     let out = Path::new("/tmp/ballsofJiaTan");
-    libroast::compress::tarxz(out, workdir, &updated_paths, true)?;
+    libroast::compress::tarxz(out, workdir, &updated_paths, true, None, 0)?;
     let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -118,7 +121,7 @@ fn is_zst_tarball() -> io::Result<()>
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
     let out = Path::new("/tmp/ballsfacebook");
-    libroast::compress::tarzst(out, workdir, &updated_paths, true)?;
+    libroast::compress::tarzst(out, workdir, &updated_paths, true, None, 0, None)?;
     let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -138,7 +141,7 @@ fn is_bz2_tarball() -> io::Result<()>
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
     let out = Path::new("/tmp/ballswhatsbz");
-    libroast::compress::tarbz2(out, workdir, &updated_paths, true)?;
+    libroast::compress::tarbz2(out, workdir, &updated_paths, true, None)?;
     let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
@@ -158,9 +161,924 @@ fn is_vanilla_tarball() -> io::Result<()>
     let mut updated_paths: Vec<PathBuf> = Vec::new();
     get_all_files(&mut updated_paths, workdir)?;
     let out = Path::new("/tmp/ballsvanillacreampie");
-    libroast::compress::vanilla(out, workdir, &updated_paths, true)?;
+    libroast::compress::vanilla(out, workdir, &updated_paths, true, None)?;
+    let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
+    info!(?res);
+    assert!(res.is_ok());
+    Ok(())
+}
+
+#[test]
+fn is_lz4_tarball() -> io::Result<()>
+{
+    let src = Path::new(MANIFEST_DIR).join("tests");
+    let tmp_binding = tempfile::TempDir::new().map_err(|err| {
+        error!(?err, "Failed to create temporary directory");
+        err
+    })?;
+    let workdir = &tmp_binding.path();
+    libroast::utils::copy_dir_all(src, workdir)?;
+    let mut updated_paths: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut updated_paths, workdir)?;
+    let out = &workdir.join("ballsofyann.tar.lz4");
+    libroast::compress::tarlz4(out, workdir, &updated_paths, true, None)?;
     let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
     info!(?res);
     assert!(res.is_ok());
+    let outdir = tempfile::TempDir::new()?;
+    libroast::decompress::tarlz4(outdir.path(), out)?;
+    assert!(outdir.path().join("shame.rs").is_file());
+    Ok(())
+}
+
+#[test]
+fn is_lzip_tarball() -> io::Result<()>
+{
+    let src = Path::new(MANIFEST_DIR).join("tests");
+    let tmp_binding = tempfile::TempDir::new().map_err(|err| {
+        error!(?err, "Failed to create temporary directory");
+        err
+    })?;
+    let workdir = &tmp_binding.path();
+    libroast::utils::copy_dir_all(src, workdir)?;
+    let mut updated_paths: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut updated_paths, workdir)?;
+    let out = &workdir.join("ballsofantonio.tar.lz");
+    libroast::compress::tarlz(out, workdir, &updated_paths, true, None)?;
+    let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
+    info!(?res);
+    assert!(res.is_ok());
+    let outdir = tempfile::TempDir::new()?;
+    libroast::decompress::tarlz(outdir.path(), out)?;
+    assert!(outdir.path().join("shame.rs").is_file());
+    Ok(())
+}
+
+#[test]
+fn is_lzma_tarball() -> io::Result<()>
+{
+    let src = Path::new(MANIFEST_DIR).join("tests");
+    let tmp_binding = tempfile::TempDir::new().map_err(|err| {
+        error!(?err, "Failed to create temporary directory");
+        err
+    })?;
+    let workdir = &tmp_binding.path();
+    libroast::utils::copy_dir_all(src, workdir)?;
+    let mut updated_paths: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut updated_paths, workdir)?;
+    let out = &workdir.join("ballsofigor.tar.lzma");
+    libroast::compress::tarlzma(out, workdir, &updated_paths, true, None)?;
+    let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
+    info!(?res);
+    assert!(res.is_ok());
+    let outdir = tempfile::TempDir::new()?;
+    libroast::decompress::tarlzma(outdir.path(), out)?;
+    assert!(outdir.path().join("shame.rs").is_file());
+    Ok(())
+}
+
+#[test]
+fn is_br_tarball() -> io::Result<()>
+{
+    let src = Path::new(MANIFEST_DIR).join("tests");
+    let tmp_binding = tempfile::TempDir::new().map_err(|err| {
+        error!(?err, "Failed to create temporary directory");
+        err
+    })?;
+    let workdir = &tmp_binding.path();
+    libroast::utils::copy_dir_all(src, workdir)?;
+    let mut updated_paths: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut updated_paths, workdir)?;
+    let out = &workdir.join("ballsofbrotli.tar.br");
+    libroast::compress::tarbr(out, workdir, &updated_paths, true, None)?;
+    let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
+    info!(?res);
+    assert!(res.is_ok());
+    let outdir = tempfile::TempDir::new()?;
+    libroast::decompress::tarbr(outdir.path(), out)?;
+    assert!(outdir.path().join("shame.rs").is_file());
+    Ok(())
+}
+
+#[test]
+fn is_zst_tarball_with_ultra_level() -> io::Result<()>
+{
+    let src = Path::new(MANIFEST_DIR).join("tests");
+    let tmp_binding = tempfile::TempDir::new().map_err(|err| {
+        error!(?err, "Failed to create temporary directory");
+        err
+    })?;
+    let workdir = &tmp_binding.path();
+    libroast::utils::copy_dir_all(src, workdir)?;
+    let mut updated_paths: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut updated_paths, workdir)?;
+    let out = Path::new("/tmp/ballsfacebookultra");
+    libroast::compress::tarzst(out, workdir, &updated_paths, true, Some(22), 0, None)?;
+    let res = libroast::utils::is_supported_format(out).inspect_err(|err| error!(?err));
+    info!(?res);
+    assert!(res.is_ok());
+    Ok(())
+}
+
+#[test]
+fn invalid_levels_are_rejected() -> io::Result<()>
+{
+    let tmp_binding = tempfile::TempDir::new()?;
+    let workdir = &tmp_binding.path();
+    let updated_paths: Vec<PathBuf> = Vec::new();
+    let out = workdir.join("invalid.tar.gz");
+    let res = libroast::compress::targz(&out, workdir, &updated_paths, true, Some(10));
+    assert!(res.is_err_and(|err| err.kind() == io::ErrorKind::InvalidInput));
+    // No output should be created if the level is invalid.
+    assert!(!out.exists());
+    let out = workdir.join("invalid.tar.bz");
+    let res = libroast::compress::tarbz2(&out, workdir, &updated_paths, true, Some(0));
+    assert!(res.is_err_and(|err| err.kind() == io::ErrorKind::InvalidInput));
+    let out = workdir.join("invalid.tar");
+    let res = libroast::compress::vanilla(&out, workdir, &updated_paths, true, Some(1));
+    assert!(res.is_err_and(|err| err.kind() == io::ErrorKind::InvalidInput));
+    Ok(())
+}
+
+#[test]
+fn is_zst_tarball_with_dictionary() -> io::Result<()>
+{
+    let mut samples: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut samples, &Path::new(MANIFEST_DIR).join("src"))?;
+    samples.retain(|p| p.is_file());
+    let dictionary = libroast::compress::train_zstd_dict(&samples, 16384)?;
+    assert!(!dictionary.is_empty());
+    let src = Path::new(MANIFEST_DIR).join("tests");
+    let tmp_binding = tempfile::TempDir::new()?;
+    let workdir = &tmp_binding.path();
+    libroast::utils::copy_dir_all(src, workdir)?;
+    let mut updated_paths: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut updated_paths, workdir)?;
+    let out = &workdir.join("ballsofdictionary.tar.zst");
+    libroast::compress::tarzst(out, workdir, &updated_paths, true, None, 0, Some(&dictionary))?;
+    let outdir = tempfile::TempDir::new()?;
+    // The dictionary is required to decompress it.
+    assert!(libroast::decompress::tarzst(outdir.path(), out, None).is_err());
+    let outdir = tempfile::TempDir::new()?;
+    libroast::decompress::tarzst(outdir.path(), out, Some(&dictionary))?;
+    assert!(outdir.path().join("shame.rs").is_file());
+    Ok(())
+}
+
+/// Creates an uncompressed tarball in memory split in two halves. The files
+/// `first.txt` and `second.txt` are in separate halves so a decoder that stops
+/// after the first member or frame misses `second.txt`.
+fn split_tarball() -> io::Result<(Vec<u8>, Vec<u8>)>
+{
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, content) in [("first.txt", vec![b'a'; 8192]), ("second.txt", vec![b'b'; 8192])]
+    {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, content.as_slice())?;
+    }
+    let tarball = builder.into_inner()?;
+    // Header and data of `first.txt` are 512 + 8192 bytes.
+    let (first, second) = tarball.split_at(512 + 8192);
+    Ok((first.to_vec(), second.to_vec()))
+}
+
+#[test]
+fn multi_member_gz_is_fully_extracted() -> io::Result<()>
+{
+    let (first, second) = split_tarball()?;
+    let mut concatenated: Vec<u8> = Vec::new();
+    for half in [first, second]
+    {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&half)?;
+        concatenated.extend(encoder.finish()?);
+    }
+    let tmp_binding = tempfile::TempDir::new()?;
+    let out = tmp_binding.path().join("multi.tar.gz");
+    std::fs::write(&out, concatenated)?;
+    let outdir = tempfile::TempDir::new()?;
+    libroast::decompress::targz(outdir.path(), &out)?;
+    assert!(outdir.path().join("first.txt").is_file());
+    assert!(outdir.path().join("second.txt").is_file());
+    Ok(())
+}
+
+#[test]
+fn multi_frame_zst_is_fully_extracted() -> io::Result<()>
+{
+    let (first, second) = split_tarball()?;
+    let mut concatenated: Vec<u8> = Vec::new();
+    for half in [first, second]
+    {
+        concatenated.extend(zstd::encode_all(half.as_slice(), 3)?);
+    }
+    let tmp_binding = tempfile::TempDir::new()?;
+    let out = tmp_binding.path().join("multi.tar.zst");
+    std::fs::write(&out, concatenated)?;
+    let outdir = tempfile::TempDir::new()?;
+    libroast::decompress::tarzst(outdir.path(), &out, None)?;
+    assert!(outdir.path().join("first.txt").is_file());
+    assert!(outdir.path().join("second.txt").is_file());
+    Ok(())
+}
+
+#[test]
+fn multi_stream_xz_is_fully_extracted() -> io::Result<()>
+{
+    let (first, second) = split_tarball()?;
+    let mut concatenated: Vec<u8> = Vec::new();
+    for half in [first, second]
+    {
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(&half)?;
+        concatenated.extend(encoder.finish()?);
+    }
+    let tmp_binding = tempfile::TempDir::new()?;
+    let out = tmp_binding.path().join("multi.tar.xz");
+    std::fs::write(&out, concatenated)?;
+    let outdir = tempfile::TempDir::new()?;
+    libroast::decompress::tarxz(outdir.path(), &out)?;
+    assert!(outdir.path().join("first.txt").is_file());
+    assert!(outdir.path().join("second.txt").is_file());
+    Ok(())
+}
+
+#[test]
+fn codec_roundtrips_in_memory() -> io::Result<()>
+{
+    use libroast::{
+        codec::{
+            Codec,
+            CodecOptions,
+        },
+        common::Compression,
+    };
+    let src = Path::new(MANIFEST_DIR).join("tests");
+    let tmp_binding = tempfile::TempDir::new()?;
+    let workdir = &tmp_binding.path();
+    libroast::utils::copy_dir_all(src, workdir)?;
+    let mut updated_paths: Vec<PathBuf> = Vec::new();
+    get_all_files(&mut updated_paths, workdir)?;
+    for compression in [
+        Compression::Gz,
+        Compression::Xz,
+        Compression::Zst,
+        Compression::Bz2,
+        Compression::Lz4,
+        Compression::Lz,
+        Compression::Lzma,
+        Compression::Br,
+        Compression::Not,
+    ]
+    {
+        let options = CodecOptions::default();
+        let tarball = libroast::compress::tar_into(
+            Vec::new(),
+            compression,
+            workdir,
+            &updated_paths,
+            true,
+            &options,
+        )?;
+        let mut ar = tar::Archive::new(compression.decoder(tarball.as_slice(), &options)?);
+        let has_shame = ar.entries()?.flatten().any(|entry| {
+            entry.path().is_ok_and(|p| p.file_name().is_some_and(|name| name == "shame.rs"))
+        });
+        assert!(has_shame, "{} tarball is missing shame.rs", compression);
+    }
+    Ok(())
+}
+
+#[test]
+fn encoders_report_errors_of_the_trailer() -> io::Result<()>
+{
+    use libroast::{
+        codec::{
+            Codec,
+            CodecOptions,
+        },
+        common::Compression,
+    };
+    use std::{
+        io::Write,
+        sync::{
+            Arc,
+            atomic::{
+                AtomicBool,
+                Ordering,
+            },
+        },
+    };
+    /// Fails every write once `full` is set e.g. like a full disk.
+    struct FailingWriter
+    {
+        full: Arc<AtomicBool>,
+    }
+    impl Write for FailingWriter
+    {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize>
+        {
+            match self.full.load(Ordering::Relaxed)
+            {
+                true => Err(io::Error::new(io::ErrorKind::StorageFull, "disk is full")),
+                false => Ok(buf.len()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+    for compression in [Compression::Gz, Compression::Xz, Compression::Zst, Compression::Br]
+    {
+        let full = Arc::new(AtomicBool::new(false));
+        let mut encoder = compression
+            .encoder(FailingWriter { full: Arc::clone(&full) }, &CodecOptions::default())?;
+        // Small inputs stay in the buffers of the encoder until it is finished.
+        encoder.write_all(b"roast")?;
+        full.store(true, Ordering::Relaxed);
+        let res = encoder.finish();
+        assert!(
+            res.is_err_and(|err| err.kind() == io::ErrorKind::StorageFull),
+            "{} encoder ignored the failed trailer",
+            compression
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn failed_tarball_leaves_outpath_untouched() -> io::Result<()>
+{
+    let tmp_binding = tempfile::TempDir::new()?;
+    let workdir = &tmp_binding.path();
+    let outdir = tempfile::TempDir::new()?;
+    let out = outdir.path().join("untouched.tar.gz");
+    std::fs::write(&out, b"previous tarball")?;
+    // A missing file makes the tar builder fail halfway.
+    let updated_paths = [workdir.join("missing.rs")];
+    let res = libroast::compress::targz(&out, workdir, &updated_paths, true, None);
+    assert!(res.is_err());
+    assert_eq!(std::fs::read(&out)?, b"previous tarball");
+    // The temporary file is cleaned up as well.
+    assert_eq!(std::fs::read_dir(outdir.path())?.count(), 1);
+    Ok(())
+}
+
+/// Appends an entry without the path validation of `tar::Header::set_path()`
+/// so that malicious tarballs can be built.
+fn append_raw_entry(
+    builder: &mut tar::Builder<Vec<u8>>,
+    path: &str,
+    entry_type: tar::EntryType,
+    link_name: Option<&str>,
+    mode: u32,
+) -> io::Result<()>
+{
+    let content: &[u8] = if entry_type.is_file() { b"pwned" } else { b"" };
+    let mut header = tar::Header::new_gnu();
+    header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+    header.set_entry_type(entry_type);
+    if let Some(link_name) = link_name
+    {
+        header.set_link_name(link_name)?;
+    }
+    header.set_size(content.len() as u64);
+    header.set_mode(mode);
+    header.set_cksum();
+    builder.append(&header, content)
+}
+
+#[test]
+fn unsafe_entries_are_rejected_or_sanitized() -> io::Result<()>
+{
+    use libroast::{
+        codec::CodecOptions,
+        common::Compression,
+        decompress::{
+            ExtractOptions,
+            ViolationKind,
+        },
+    };
+    use std::os::unix::fs::PermissionsExt;
+    use tar::EntryType;
+    let mut builder = tar::Builder::new(Vec::new());
+    append_raw_entry(&mut builder, "../evil.txt", EntryType::Regular, None, 0o644)?;
+    append_raw_entry(&mut builder, "/absolute.txt", EntryType::Regular, None, 0o644)?;
+    append_raw_entry(&mut builder, "passwd", EntryType::Symlink, Some("/etc/passwd"), 0o777)?;
+    append_raw_entry(&mut builder, "a/up", EntryType::Symlink, Some("../../evil.txt"), 0o777)?;
+    append_raw_entry(&mut builder, "hard", EntryType::Link, Some("../evil.txt"), 0o644)?;
+    append_raw_entry(&mut builder, "null", EntryType::Char, None, 0o666)?;
+    append_raw_entry(&mut builder, "suid.sh", EntryType::Regular, None, 0o4755)?;
+    append_raw_entry(&mut builder, "a/good.txt", EntryType::Regular, None, 0o644)?;
+    append_raw_entry(&mut builder, "a/b/good", EntryType::Symlink, Some("../good.txt"), 0o777)?;
+    // Each link stays inside on its own but `d/..` is the parent of the output
+    // directory once `d` is extracted.
+    append_raw_entry(&mut builder, "d", EntryType::Symlink, Some("."), 0o777)?;
+    append_raw_entry(&mut builder, "e", EntryType::Symlink, Some("d/.."), 0o777)?;
+    append_raw_entry(&mut builder, "a/f", EntryType::Symlink, Some("../d/d/a/.."), 0o777)?;
+    append_raw_entry(&mut builder, "chained", EntryType::Link, Some("d/../evil.txt"), 0o644)?;
+    let tarball = builder.into_inner()?;
+
+    let tmp_binding = tempfile::TempDir::new()?;
+    let outdir = tmp_binding.path().join("out");
+    let violations = libroast::decompress::unpack_from(
+        tarball.as_slice(),
+        Compression::Not,
+        &outdir,
+        &CodecOptions::default(),
+        &ExtractOptions::default(),
+    )?;
+    let kinds: Vec<ViolationKind> = violations.iter().map(|violation| violation.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ViolationKind::ParentDir,
+            ViolationKind::AbsolutePath,
+            ViolationKind::SymlinkOutside,
+            ViolationKind::SymlinkOutside,
+            ViolationKind::HardlinkOutside,
+            ViolationKind::SpecialFile,
+            ViolationKind::SpecialModeBits,
+            ViolationKind::SymlinkOutside,
+            ViolationKind::HardlinkOutside,
+        ]
+    );
+    assert!(!tmp_binding.path().join("evil.txt").exists());
+    assert!(outdir.join("absolute.txt").is_file());
+    assert!(outdir.join("passwd").symlink_metadata().is_err());
+    assert!(outdir.join("a/up").symlink_metadata().is_err());
+    assert!(outdir.join("hard").symlink_metadata().is_err());
+    assert!(outdir.join("null").symlink_metadata().is_err());
+    assert_eq!(outdir.join("suid.sh").metadata()?.permissions().mode() & 0o7000, 0);
+    assert!(outdir.join("a/b/good").is_file());
+    assert!(outdir.join("d").is_symlink());
+    assert!(outdir.join("e").symlink_metadata().is_err());
+    assert!(outdir.join("a/f").is_symlink());
+    assert!(outdir.join("chained").symlink_metadata().is_err());
+
+    // Opting out extracts the links as they are.
+    let outdir = tmp_binding.path().join("unsafe");
+    std::fs::write(tmp_binding.path().join("evil.txt"), b"outside")?;
+    let violations = libroast::decompress::unpack_from(
+        tarball.as_slice(),
+        Compression::Not,
+        &outdir,
+        &CodecOptions::default(),
+        &ExtractOptions { safe: false, ..Default::default() },
+    );
+    // Tar still refuses to create the hardlink to a file outside of the output
+    // directory.
+    assert!(violations.is_err());
+    assert!(outdir.join("passwd").symlink_metadata().is_ok());
+    Ok(())
+}
+
+#[test]
+fn extraction_limits_are_enforced() -> io::Result<()>
+{
+    use libroast::{
+        codec::CodecOptions,
+        common::Compression,
+        decompress::{
+            ExtractOptions,
+            Limit,
+        },
+    };
+    let (first, second) = split_tarball()?;
+    let tarball = [first, second].concat();
+    let extract = |compression: Compression, tarball: &[u8], extract_options: ExtractOptions| {
+        let outdir = tempfile::TempDir::new()?;
+        let res = libroast::decompress::unpack_from(
+            tarball,
+            compression,
+            outdir.path(),
+            &CodecOptions::default(),
+            &extract_options,
+        );
+        match res.map_err(libroast::Error::from)
+        {
+            Err(libroast::Error::Limit(exceeded)) => Ok(Some(exceeded.limit)),
+            Err(err) => Err(io::Error::from(err)),
+            Ok(_) => Ok(None),
+        }
+    };
+    let options = ExtractOptions { max_entries: Some(1), ..Default::default() };
+    assert_eq!(extract(Compression::Not, &tarball, options)?, Some(Limit::Entries));
+    let options = ExtractOptions { max_file_size: Some(4096), ..Default::default() };
+    assert_eq!(extract(Compression::Not, &tarball, options)?, Some(Limit::FileSize));
+    let options = ExtractOptions { max_total_size: Some(8192 + 4096), ..Default::default() };
+    assert_eq!(extract(Compression::Not, &tarball, options)?, Some(Limit::TotalSize));
+    let options = ExtractOptions {
+        max_entries: Some(2),
+        max_file_size: Some(8192),
+        max_total_size: Some(8192 * 2),
+        ..Default::default()
+    };
+    assert_eq!(extract(Compression::Not, &tarball, options)?, None);
+
+    // 16 MiB of zeroes compress to a few KiB.
+    let mut builder = tar::Builder::new(Vec::new());
+    let zeroes = vec![0u8; 16 * 1024 * 1024];
+    let mut header = tar::Header::new_gnu();
+    header.set_size(zeroes.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, "bomb", zeroes.as_slice())?;
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(&builder.into_inner()?)?;
+    let bomb = encoder.finish()?;
+    let options = ExtractOptions { max_ratio: Some(100), ..Default::default() };
+    assert_eq!(extract(Compression::Gz, &bomb, options)?, Some(Limit::Ratio));
+    let options = ExtractOptions { max_ratio: Some(100_000), ..Default::default() };
+    assert_eq!(extract(Compression::Gz, &bomb, options)?, None);
+    Ok(())
+}
+
+#[test]
+fn entries_are_listed_without_extracting() -> io::Result<()>
+{
+    use libroast::{
+        codec::CodecOptions,
+        common::Compression,
+    };
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(5);
+    header.set_mode(0o640);
+    header.set_mtime(1_700_000_000);
+    header.set_username("geeko")?;
+    header.set_cksum();
+    builder.append_data(&mut header, "src/main.rs", b"hello".as_slice())?;
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    header.set_cksum();
+    builder.append_link(&mut header, "src/lib.rs", "main.rs")?;
+    let tarball = zstd::encode_all(builder.into_inner()?.as_slice(), 3)?;
+
+    let mut entries = Vec::new();
+    libroast::decompress::list_from(
+        tarball.as_slice(),
+        Compression::Zst,
+        &CodecOptions::default(),
+        |entry| {
+            entries.push(entry);
+            Ok(())
+        },
+    )?;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].path, Path::new("src/main.rs"));
+    assert_eq!(entries[0].type_name(), "file");
+    assert_eq!(entries[0].size, 5);
+    assert_eq!(entries[0].mode, 0o640);
+    assert_eq!(entries[0].mtime, 1_700_000_000);
+    assert_eq!(entries[0].username.as_deref(), Some("geeko"));
+    assert_eq!(entries[1].type_name(), "symlink");
+    assert_eq!(entries[1].link_name.as_deref(), Some(Path::new("main.rs")));
+    Ok(())
+}
+
+#[test]
+fn selected_entries_are_extracted_with_stripped_components() -> io::Result<()>
+{
+    use glob::Pattern;
+    use libroast::{
+        codec::CodecOptions,
+        common::Compression,
+        decompress::ExtractOptions,
+    };
+    let mut builder = tar::Builder::new(Vec::new());
+    for name in [
+        "foo-1.0/Cargo.lock",
+        "foo-1.0/foo.spec",
+        "foo-1.0/vendor/bar/Cargo.lock",
+        "foo-1.0/vendor/bar/src/lib.rs",
+        "foo-1.0/vendor/baz/src/lib.rs",
+    ]
+    {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, b"data".as_slice())?;
+    }
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Link);
+    header.set_size(0);
+    header.set_cksum();
+    builder.append_link(&mut header, "foo-1.0/vendor/baz/Cargo.lock", "foo-1.0/Cargo.lock")?;
+    let tarball = builder.into_inner()?;
+    let extract = |extract_options: &ExtractOptions| -> io::Result<Vec<PathBuf>> {
+        let outdir = tempfile::TempDir::new()?;
+        libroast::decompress::unpack_from(
+            tarball.as_slice(),
+            Compression::Not,
+            outdir.path(),
+            &CodecOptions::default(),
+            extract_options,
+        )?;
+        let mut files = Vec::new();
+        get_all_files(&mut files, &outdir.path().canonicalize()?)?;
+        let outdir = outdir.path().canonicalize()?;
+        let mut files: Vec<PathBuf> = files
+            .iter()
+            .filter_map(|f| f.strip_prefix(&outdir).ok())
+            .map(Path::to_path_buf)
+            .collect();
+        files.sort();
+        Ok(files)
+    };
+    let patterns = |patterns: &[&str]| -> io::Result<Vec<Pattern>> {
+        patterns.iter().map(|p| Pattern::new(p).map_err(io::Error::other)).collect()
+    };
+
+    let options = ExtractOptions {
+        include: patterns(&["Cargo.lock"])?,
+        strip_components: 1,
+        ..Default::default()
+    };
+    assert_eq!(extract(&options)?, [PathBuf::from("Cargo.lock")]);
+
+    // Equal matches are excluded.
+    let options = ExtractOptions {
+        include: patterns(&["**/Cargo.lock"])?,
+        exclude: patterns(&["vendor/baz/*"])?,
+        strip_components: 1,
+        ..Default::default()
+    };
+    assert_eq!(
+        extract(&options)?,
+        [PathBuf::from("Cargo.lock"), PathBuf::from("vendor/bar/Cargo.lock")]
+    );
+
+    // An include overrides the exclude of a parent directory.
+    let options = ExtractOptions {
+        include: patterns(&["foo-1.0/*.spec", "foo-1.0/vendor/bar/src"])?,
+        exclude: patterns(&["foo-1.0/vendor"])?,
+        ..Default::default()
+    };
+    assert_eq!(
+        extract(&options)?,
+        [PathBuf::from("foo-1.0/foo.spec"), PathBuf::from("foo-1.0/vendor/bar/src/lib.rs")]
+    );
+
+    // Hardlink targets are stripped as well.
+    let options = ExtractOptions { strip_components: 1, ..Default::default() };
+    assert_eq!(
+        extract(&options)?,
+        [
+            PathBuf::from("Cargo.lock"),
+            PathBuf::from("foo.spec"),
+            PathBuf::from("vendor/bar/Cargo.lock"),
+            PathBuf::from("vendor/bar/src/lib.rs"),
+            PathBuf::from("vendor/baz/Cargo.lock"),
+            PathBuf::from("vendor/baz/src/lib.rs"),
+        ]
+    );
+
+    // Hardlinks to skipped entries are skipped.
+    let options = ExtractOptions { strip_components: 2, ..Default::default() };
+    assert_eq!(
+        extract(&options)?,
+        [
+            PathBuf::from("bar/Cargo.lock"),
+            PathBuf::from("bar/src/lib.rs"),
+            PathBuf::from("baz/src/lib.rs"),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn a_single_matching_file_is_written_out() -> io::Result<()>
+{
+    use glob::Pattern;
+    use libroast::{
+        codec::CodecOptions,
+        common::Compression,
+    };
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, content) in [
+        ("foo-1.0/VERSION", b"1.0".as_slice()),
+        ("foo-1.0/Cargo.toml", b"[package]".as_slice()),
+        ("foo-1.0/vendor/bar/Cargo.toml", b"[workspace]".as_slice()),
+    ]
+    {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, content)?;
+    }
+    let tarball = builder.into_inner()?;
+    let cat = |pattern: &str| -> Result<Vec<u8>, libroast::Error> {
+        let pattern = Pattern::new(pattern).map_err(io::Error::other)?;
+        let mut out = Vec::new();
+        libroast::decompress::cat_from(
+            tarball.as_slice(),
+            Compression::Not,
+            &CodecOptions::default(),
+            &pattern,
+            &mut out,
+        )?;
+        Ok(out)
+    };
+    assert_eq!(cat("foo-1.0/VERSION")?, b"1.0");
+    assert_eq!(cat("*/Cargo.toml")?, b"[package]");
+    assert!(matches!(cat("*/README.md"), Err(libroast::Error::Filter { .. })));
+    assert!(matches!(cat("**/Cargo.toml"), Err(libroast::Error::Filter { .. })));
+    // Directories never match.
+    assert!(matches!(cat("foo-1.0"), Err(libroast::Error::Filter { .. })));
+    Ok(())
+}
+
+#[test]
+fn zip_and_7z_archives_are_extracted() -> io::Result<()>
+{
+    use libroast::{
+        common::{
+            ArchiveFormat,
+            SupportedFormat,
+        },
+        decompress::{
+            ExtractOptions,
+            Violation,
+            ViolationKind,
+        },
+    };
+    use std::io::Write;
+    let tmp = tempfile::tempdir()?;
+    let zip_path = tmp.path().join("foo-1.0.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path)?);
+    let options = zip::write::SimpleFileOptions::default().unix_permissions(0o755);
+    zip.add_directory("foo-1.0/", options)?;
+    zip.start_file("foo-1.0/configure", options)?;
+    zip.write_all(b"#!/bin/sh")?;
+    zip.add_symlink("foo-1.0/setup", "configure", options)?;
+    zip.start_file("../evil.txt", options)?;
+    zip.write_all(b"evil")?;
+    zip.start_file("/abs.txt", options)?;
+    zip.write_all(b"abs")?;
+    zip.finish()?;
+
+    let src = tmp.path().join("src");
+    std::fs::create_dir_all(src.join("bar-1.0/docs"))?;
+    std::fs::write(src.join("bar-1.0/docs/README"), b"bar")?;
+    let sevenz_path = tmp.path().join("bar-1.0.7z");
+    sevenz_rust::compress_to_path(&src, &sevenz_path).map_err(io::Error::other)?;
+
+    let extract = |format: ArchiveFormat, path: &Path, outdir: &Path| {
+        libroast::decompress::unpack_archive(format, path, outdir, &ExtractOptions::default())
+    };
+
+    assert!(matches!(
+        libroast::utils::is_supported_format(&zip_path),
+        Ok(SupportedFormat::Archive(ArchiveFormat::Zip, _))
+    ));
+    let outdir = tmp.path().join("zip");
+    // Unsafe paths are reported before the conversion drops or sanitizes them.
+    assert_eq!(
+        extract(ArchiveFormat::Zip, &zip_path, &outdir)?,
+        vec![
+            Violation { path: PathBuf::from("../evil.txt"), kind: ViolationKind::ParentDir },
+            Violation { path: PathBuf::from("/abs.txt"), kind: ViolationKind::AbsolutePath },
+        ]
+    );
+    assert_eq!(std::fs::read(outdir.join("abs.txt"))?, b"abs");
+    assert_eq!(std::fs::read(outdir.join("foo-1.0/configure"))?, b"#!/bin/sh");
+    assert_eq!(std::fs::read_link(outdir.join("foo-1.0/setup"))?, Path::new("configure"));
+    assert!(!tmp.path().join("evil.txt").exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(outdir.join("foo-1.0/configure"))?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    assert!(matches!(
+        libroast::utils::is_supported_format(&sevenz_path),
+        Ok(SupportedFormat::Archive(ArchiveFormat::SevenZ, _))
+    ));
+    let outdir = tmp.path().join("7z");
+    assert!(extract(ArchiveFormat::SevenZ, &sevenz_path, &outdir)?.is_empty());
+    assert_eq!(std::fs::read(outdir.join("bar-1.0/docs/README"))?, b"bar");
+
+    // Consumers may stop reading early.
+    let (entries, _) =
+        libroast::decompress::archive_as_tar(ArchiveFormat::Zip, &zip_path, |tar| {
+            let mut ar = tar::Archive::new(tar);
+            let first =
+                ar.entries()?.next().transpose()?.map(|entry| entry.path().map(|p| p.into_owned()));
+            first.transpose()
+        })?;
+    assert_eq!(entries.as_deref(), Some(Path::new("foo-1.0")));
+    Ok(())
+}
+
+#[test]
+fn zip_expansion_ratio_is_measured_against_the_archive() -> io::Result<()>
+{
+    use libroast::{
+        common::ArchiveFormat,
+        decompress::{
+            ExtractOptions,
+            Limit,
+        },
+    };
+    use std::io::Write;
+    let tmp = tempfile::tempdir()?;
+    // 16 MiB of zeroes deflate to a few KiB.
+    let zip_path = tmp.path().join("bomb.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path)?);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("bomb", options)?;
+    zip.write_all(&vec![0u8; 16 * 1024 * 1024])?;
+    zip.finish()?;
+
+    let extract = |max_ratio: u64| {
+        let outdir = tempfile::TempDir::new()?;
+        let extract_options = ExtractOptions { max_ratio: Some(max_ratio), ..Default::default() };
+        let res = libroast::decompress::unpack_archive(
+            ArchiveFormat::Zip,
+            &zip_path,
+            outdir.path(),
+            &extract_options,
+        );
+        match res.map_err(libroast::Error::from)
+        {
+            Err(libroast::Error::Limit(exceeded)) => Ok(Some(exceeded.limit)),
+            Err(err) => Err(io::Error::from(err)),
+            Ok(_) => Ok(None),
+        }
+    };
+    assert_eq!(extract(100)?, Some(Limit::Ratio));
+    assert_eq!(extract(100_000)?, None);
+    Ok(())
+}
+
+#[test]
+fn formats_are_sniffed_from_magic_bytes() -> io::Result<()>
+{
+    use libroast::{
+        codec::CodecOptions,
+        common::{
+            Compression,
+            InputFormat,
+            SupportedFormat,
+        },
+        utils::sniff_format,
+    };
+    let tmp = tempfile::tempdir()?;
+    std::fs::write(tmp.path().join("README"), "roast")?;
+    let files = [tmp.path().join("README")];
+    for (compression, format) in [
+        (Compression::Gz, InputFormat::Gz),
+        (Compression::Xz, InputFormat::Xz),
+        (Compression::Zst, InputFormat::Zst),
+        (Compression::Bz2, InputFormat::Bz2),
+        (Compression::Lz4, InputFormat::Lz4),
+        (Compression::Lz, InputFormat::Lz),
+        (Compression::Lzma, InputFormat::Lzma),
+        (Compression::Not, InputFormat::Tar),
+    ]
+    {
+        let tarball = libroast::compress::tar_into(
+            Vec::new(),
+            compression,
+            tmp.path(),
+            &files,
+            true,
+            &CodecOptions::default(),
+        )?;
+        assert_eq!(sniff_format(&tarball), Some(format), "{} was not detected", compression);
+    }
+
+    // Old v7 tarballs have no magic but a valid checksum.
+    let mut header = tar::Header::new_old();
+    header.set_path("README")?;
+    header.set_size(5);
+    header.set_cksum();
+    assert_eq!(sniff_format(header.as_bytes()), Some(InputFormat::Tar));
+    let mut corrupted = header.as_bytes().to_vec();
+    corrupted[0] = b'X';
+    assert_eq!(sniff_format(&corrupted), None);
+
+    // The magic bytes win over a misleading file extension.
+    let gz = tmp.path().join("source.tar.zst");
+    libroast::compress::targz(&gz, tmp.path(), &files, true, None)?;
+    assert!(matches!(
+        libroast::utils::is_supported_format(&gz),
+        Ok(SupportedFormat::Compressed(Compression::Gz, _))
+    ));
+
+    let png = tmp.path().join("source.rar");
+    std::fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")?;
+    let err = libroast::utils::is_supported_format(&png)
+        .err()
+        .ok_or(io::Error::other("A png is not an archive"))?;
+    assert_eq!(err.to_string(), "Unsupported archive format: rar (detected MIME type: image/png)");
     Ok(())
 }
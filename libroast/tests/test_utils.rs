@@ -60,3 +60,44 @@ fn test_copy_project_to_another_temp_dir() -> io::Result<()>
     assert!(ls.count() > 0);
     Ok(())
 }
+
+#[test]
+fn test_threads_are_resolved() -> io::Result<()>
+{
+    let available = utils::available_threads();
+    info!(?available);
+    assert!(available >= 1);
+    let parallelism = std::thread::available_parallelism()?.get() as u32;
+    assert!(available <= parallelism);
+    assert_eq!(utils::resolve_threads(0), available);
+    assert_eq!(utils::resolve_threads(3), 3);
+    Ok(())
+}
+
+#[test]
+fn test_compression_is_detected_from_magic_bytes() -> io::Result<()>
+{
+    use libroast::{
+        codec::CodecOptions,
+        common::Compression,
+    };
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let archive_files = [src.join("test_utils.rs")];
+    for compression in
+        [Compression::Gz, Compression::Xz, Compression::Zst, Compression::Bz2, Compression::Not]
+    {
+        let tarball = libroast::compress::tar_into(
+            Vec::new(),
+            compression,
+            &src,
+            &archive_files,
+            true,
+            &CodecOptions::default(),
+        )?;
+        let detected = utils::detect_compression(&tarball[..512])
+            .map_err(|err| io::Error::new(io::ErrorKind::Unsupported, err.to_string()))?;
+        assert_eq!(detected.to_string(), compression.to_string());
+    }
+    assert!(utils::detect_compression(b"definitely not a tarball").is_err());
+    Ok(())
+}
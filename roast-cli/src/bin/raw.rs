@@ -1,9 +1,12 @@
-use roast_cli::raw::raw_cli_stub;
+use roast_cli::{
+    exit::exit_code,
+    raw::raw_cli_stub,
+};
 
 fn main()
 {
-    if raw_cli_stub().is_err()
+    if let Err(err) = raw_cli_stub()
     {
-        std::process::exit(1)
+        std::process::exit(exit_code(&err))
     }
 }
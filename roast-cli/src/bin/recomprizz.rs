@@ -1,9 +1,12 @@
-use roast_cli::recomprizz::recomprizz_cli_stub;
+use roast_cli::{
+    exit::exit_code,
+    recomprizz::recomprizz_cli_stub,
+};
 
 pub fn main()
 {
-    if recomprizz_cli_stub().is_err()
+    if let Err(err) = recomprizz_cli_stub()
     {
-        std::process::exit(1)
+        std::process::exit(exit_code(&err))
     }
 }
@@ -1,9 +1,12 @@
-use roast_cli::roast::roast_cli_stub;
+use roast_cli::{
+    exit::exit_code,
+    roast::roast_cli_stub,
+};
 
 fn main()
 {
-    if roast_cli_stub().is_err()
+    if let Err(err) = roast_cli_stub()
     {
-        std::process::exit(1)
+        std::process::exit(exit_code(&err))
     }
 }
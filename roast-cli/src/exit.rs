@@ -0,0 +1,29 @@
+//! Exit codes of the binaries. The values follow `sysexits.h` so that scripts
+//! can tell the failures apart.
+
+use libroast::Error;
+
+/// Invalid or missing arguments.
+pub const EXIT_USAGE: i32 = 64;
+/// The archive format is not supported or the archive exceeds the extraction
+/// limits.
+pub const EXIT_DATAERR: i32 = 65;
+/// Paths could not be resolved or filtered.
+pub const EXIT_NOINPUT: i32 = 66;
+/// Git operations failed e.g. cloning or finding a revision.
+pub const EXIT_UNAVAILABLE: i32 = 69;
+/// Any other I/O error.
+pub const EXIT_IOERR: i32 = 74;
+
+/// Maps a `libroast::Error` to the exit code of the binaries.
+pub fn exit_code(err: &Error) -> i32
+{
+    match err
+    {
+        Error::InvalidArgument { .. } => EXIT_USAGE,
+        Error::Format(_) | Error::Limit(_) => EXIT_DATAERR,
+        Error::Filter { .. } => EXIT_NOINPUT,
+        Error::Git(_) => EXIT_UNAVAILABLE,
+        Error::Io(_) => EXIT_IOERR,
+    }
+}
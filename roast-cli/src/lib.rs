@@ -1,4 +1,5 @@
 pub mod cli;
+pub mod exit;
 pub mod raw;
 pub mod recomprizz;
 pub mod roast;
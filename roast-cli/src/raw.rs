@@ -3,10 +3,10 @@ use crate::{
     raw_opts,
 };
 use clap::Parser;
-use std::io;
+use libroast::error::Result;
 
-pub fn raw_cli_stub() -> io::Result<()>
+pub fn raw_cli_stub() -> Result<()>
 {
     let raw_args = cli::RawArgs::parse();
-    raw_opts(raw_args, true)
+    raw_opts(&raw_args, true)
 }
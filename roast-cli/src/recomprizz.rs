@@ -3,9 +3,9 @@ use crate::{
     recomprizz_opts,
 };
 use clap::Parser;
-use std::io;
+use libroast::error::Result;
 
-pub fn recomprizz_cli_stub() -> io::Result<()>
+pub fn recomprizz_cli_stub() -> Result<()>
 {
     let recomprizz_args = RecomprizzArgs::parse();
     recomprizz_opts(recomprizz_args)
@@ -3,9 +3,9 @@ use crate::{
     roast_opts,
 };
 use clap::Parser;
-use std::io;
+use libroast::error::Result;
 
-pub fn roast_cli_stub() -> io::Result<()>
+pub fn roast_cli_stub() -> Result<()>
 {
     let roast_args = cli::RoastArgs::parse();
     roast_opts(&roast_args, true)
@@ -0,0 +1,49 @@
+use clap::Parser;
+use libroast::{
+    Error,
+    common::UnsupportedFormat,
+    decompress::{
+        Limit,
+        LimitExceeded,
+    },
+    operations::cli::RoastScmArgs,
+};
+use roast_cli::{
+    exit::exit_code,
+    roast_scm_opts,
+};
+use std::io;
+
+#[test]
+fn errors_map_to_sysexits_codes() -> io::Result<()>
+{
+    assert_eq!(exit_code(&Error::invalid_argument("No target provided.")), 64);
+    let format = UnsupportedFormat { ext: "balls.rar".to_string(), mime_type: None };
+    assert_eq!(exit_code(&Error::Format(format)), 65);
+    let limit = LimitExceeded { limit: Limit::Entries, max: 1 };
+    assert_eq!(exit_code(&Error::Limit(limit)), 65);
+    assert_eq!(exit_code(&Error::filter("No files/directories matched src glob input")), 66);
+    assert_eq!(exit_code(&Error::Io(io::Error::other("disk full"))), 74);
+
+    // Cloning a repository that does not exist fails in git.
+    let tmp = tempfile::tempdir()?;
+    let url = format!("file://{}", tmp.path().join("missing").display());
+    let roast_scm_args = RoastScmArgs::try_parse_from([
+        "roast_scm",
+        "-U",
+        &url,
+        "--revision",
+        "main",
+        "-d",
+        &tmp.path().join("out").to_string_lossy(),
+    ])
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let Err(err) = roast_scm_opts(None, &roast_scm_args, false)
+    else
+    {
+        return Err(io::Error::other("Cloning a missing repository succeeded."));
+    };
+    assert!(matches!(err, Error::Git(_)));
+    assert_eq!(exit_code(&err), 69);
+    Ok(())
+}